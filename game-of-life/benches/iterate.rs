@@ -0,0 +1,51 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use game_of_life::bitfield::BitField;
+use game_of_life::lenia::{self, Lenia};
+use game_of_life::render::{self, RenderMode};
+use game_of_life::Field;
+
+// 比較するフィールドの大きさ（列数, 行数）
+const SIZES: [(u32, u32); 3] = [(128, 72), (1024, 1024), (4096, 4096)];
+
+// 通常のフィールド（1スレッド・全CPU）と1セル1ビットのフィールドで、1世代進める時間を比べる
+fn iterate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("iterate");
+    // 大きなフィールドは1回が長いため、計測回数を減らす
+    group.sample_size(10);
+    for (col_n, row_n) in SIZES {
+        let size = format!("{}x{}", col_n, row_n);
+        let mut field = Field::new(row_n, col_n, 0.25);
+        let mut bitfield = BitField::from_field(&field);
+        group.bench_function(BenchmarkId::new("dense", &size), |b| b.iter(|| field.iterate()));
+        field.threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        group.bench_function(BenchmarkId::new("dense-parallel", &size), |b| b.iter(|| field.iterate()));
+        group.bench_function(BenchmarkId::new("bit-packed", &size), |b| b.iter(|| bitfield.iterate()));
+    }
+    group.finish();
+}
+
+// Leniaの1ステップ（FFTによる畳み込み）の時間。画面に表示する256x256で、60fpsに収まるかを確かめる
+fn lenia(c: &mut Criterion) {
+    let mut lenia = Lenia::from_preset(256, 256, &lenia::PRESETS[0], 0);
+    c.bench_function("lenia/256x256", |b| b.iter(|| lenia.step()));
+}
+
+// テクスチャで描くときに、毎フレーム画面に映っている範囲のセルから画像を作る時間
+// 長方形で描く方法はGPUとウィンドウが必要なため、ウィンドウのHUDに表示されるフレーム時間で比べる
+fn texels(c: &mut Criterion) {
+    let mut group = c.benchmark_group("texels");
+    for (col_n, row_n) in SIZES {
+        let field = Field::new(row_n, col_n, 0.25);
+        let cells = || field.matrix.iter().enumerate().flat_map(|(x, col)| {
+            col.iter().enumerate().map(move |(y, cell)| ((x as i64, y as i64), cell))
+        });
+        group.bench_function(BenchmarkId::from_parameter(format!("{}x{}", col_n, row_n)), |b| {
+            b.iter(|| render::texels(cells(), &field.rule, RenderMode::Colour, (0, 0), col_n, row_n))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, iterate, lenia, texels);
+criterion_main!(benches);
@@ -0,0 +1,53 @@
+use std::fmt;
+use std::str::FromStr;
+
+// コマンドライン引数から "--name value" 形式のオプションの値を取り出す関数
+// 同じオプションが何度か指定されていれば、最初のものを使う
+pub fn arg_value(name: &str) -> Option<String> {
+    find_value(std::env::args(), name)
+}
+
+// "--name value" 形式のオプションの値を読み込む関数。指定がなければNoneを返し、読み込めなければエラーにする
+pub fn parse_arg<T: FromStr>(name: &str) -> Result<Option<T>, String>
+where
+    T::Err: fmt::Display,
+{
+    arg_value(name).map(|value| parse_value(name, &value)).transpose()
+}
+
+// argsの中でnameの次にある値を返す
+fn find_value(args: impl Iterator<Item = String>, name: &str) -> Option<String> {
+    args.skip_while(|arg| arg != name).nth(1)
+}
+
+// オプションnameの値valueを読み込む。エラーにはオプションの名前と値を含める
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T, String>
+where
+    T::Err: fmt::Display,
+{
+    value.trim().parse::<T>().map_err(|err| format!("invalid {} {:?}: {}", name, value, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> impl Iterator<Item = String> + '_ {
+        line.split(' ').map(String::from)
+    }
+
+    #[test]
+    fn finds_the_value_after_the_first_matching_option() {
+        assert_eq!(find_value(args("program --seed 1 --rule B3/S23 --seed 2"), "--seed"), Some("1".to_string()));
+        assert_eq!(find_value(args("program --seed 1 --rule B3/S23"), "--rule"), Some("B3/S23".to_string()));
+        assert_eq!(find_value(args("program --seed"), "--seed"), None);
+        assert_eq!(find_value(args("program --seed 1"), "--size"), None);
+    }
+
+    #[test]
+    fn parse_errors_name_the_option() {
+        assert_eq!(parse_value::<u64>("--seed", " 42 "), Ok(42));
+        let err = parse_value::<u64>("--seed", "forty").unwrap_err();
+        assert!(err.starts_with("invalid --seed \"forty\": "), "{}", err);
+    }
+}
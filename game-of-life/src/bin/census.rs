@@ -0,0 +1,78 @@
+// ランダムなスープをたくさん落ち着くまで進め、残った物体を種類ごとに数えるプログラム（apgsearchのような調査）
+// 結果はCSVに書き出すため、別々のマシンや種の範囲で調べた結果を並べて、珍しい物体を探せる
+//
+// 使い方の例:
+//   cargo run --release --bin census -- --soups 10000 --seed 0 --output census.csv
+//   cargo run --release --bin census -- --rule B36/S23 --soups 1000
+//
+// CSVの seeds の列に載ったスープは、CSVの隣の <output>.soups/<種>.rle にRLEとして書き出す
+// 調査は端のない平面で行うため、珍しい物体が見つかったら、同じ端のない平面（--backend sparse）で進めて再現する
+// フィールドの大きさは統計と書き出しに使う範囲で、飛び去ったグライダーは範囲の外で進み続け、灰を残さない:
+//   cargo run --release --bin headless -- --pattern census.soups/123.rle --backend sparse --size 256x256 --generations 5000
+
+use std::path::Path;
+use std::process;
+use std::time::Instant;
+
+use game_of_life::args::{arg_value, parse_arg};
+use game_of_life::census::{self, Census};
+use game_of_life::rule::Rule;
+
+const DEFAULT_SOUPS: u64 = 1000;           // 調べるスープの数の初期値（--soups オプションで変更できる）
+const DEFAULT_OUTPUT: &str = "census.csv"; // 書き出すファイルの名前の初期値
+const SHOWN_OBJECTS: usize = 20;           // 画面に表示する物体の種類の数
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let soups = parse_arg("--soups")?.unwrap_or(DEFAULT_SOUPS);
+    let first_seed: u64 = parse_arg("--seed")?.unwrap_or_else(rand::random);
+    let rule: Rule = parse_arg("--rule")?.unwrap_or_default();
+    let threads = parse_arg("--threads")?
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+    let output = arg_value("--output").unwrap_or_else(|| DEFAULT_OUTPUT.to_string());
+    if !census::supports(&rule) {
+        return Err(format!("the census cannot search {}: it needs a two-state rule on a square grid that does not give birth with 0 neighbours", rule));
+    }
+    let last_seed = first_seed.checked_add(soups).ok_or("--seed plus --soups is too large")?;
+
+    println!("searching {} soups (seeds {} to {}) with {} on {} threads", soups, first_seed, last_seed - 1, rule, threads);
+    let start = Instant::now();
+    let census = Census::search(rule, first_seed..last_seed, threads);
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let text = census.to_string();
+    // 集計のうち、多く現れた物体だけを表示する
+    for line in text.lines().take_while(|line| line.starts_with('#')).skip(1) {
+        println!("{}", line.trim_start_matches("# "));
+    }
+    for (apgcode, tally) in census.sorted().into_iter().take(SHOWN_OBJECTS) {
+        let line = format!("{:>10} {} {}", tally.count, apgcode, census::name(apgcode).unwrap_or(""));
+        println!("{}", line.trim_end());
+    }
+    std::fs::write(&output, text).map_err(|err| format!("failed to save {}: {}", output, err))?;
+    println!("saved {} ({:.1} soups per second)", output, soups as f64 / elapsed.max(f64::EPSILON));
+    save_soups(&census, rule, &output)
+}
+
+// CSVに載った種のスープを、CSVの隣のディレクトリにRLEとして書き出す関数
+fn save_soups(census: &Census, rule: Rule, output: &str) -> Result<(), String> {
+    let directory = Path::new(output).with_extension("soups");
+    std::fs::create_dir_all(&directory)
+        .map_err(|err| format!("failed to create {}: {}", directory.display(), err))?;
+    let mut seeds: Vec<u64> = census.sorted().iter().flat_map(|(_, tally)| tally.seeds.iter().copied()).collect();
+    seeds.sort();
+    seeds.dedup();
+    for &seed in &seeds {
+        let path = directory.join(format!("{}.rle", seed));
+        std::fs::write(&path, census::soup(seed, rule).to_pattern().to_rle())
+            .map_err(|err| format!("failed to save {}: {}", path.display(), err))?;
+    }
+    println!("saved {} soups to {}", seeds.len(), directory.display());
+    Ok(())
+}
@@ -0,0 +1,178 @@
+// ウィンドウを開かずにフィールドを指定した世代まで進め、結果をファイルに書き出すプログラム
+// GPUやディスプレイの無いマシンでも、たくさんの実行をまとめて行えるようにするためのもの
+//
+// 使い方の例:
+//   cargo run --release --bin headless -- --seed 42 --generations 1000 --output soup
+//   cargo run --release --bin headless -- --pattern glider.rle --size 64x64 --boundary torus
+//   cargo run --release --bin headless -- --pattern soup.rle --backend sparse --size 256x256
+//
+// --backend で世代を進める計算方法（dense, bit-packed, hashlife, sparse）を選べる
+// hashlifeとsparseでは端のない平面で進め、フィールドの範囲に入っているセルだけを統計や書き出しに使う
+//
+// <output>.rle に最後の世代のパターンを、<output>.png にその画像を、<output>.stats に統計の要約を書き出す
+
+use std::process;
+
+use game_of_life::args::{arg_value, parse_arg};
+use game_of_life::bitfield::BitField;
+use game_of_life::hashlife::HashLife;
+use game_of_life::manifest::{BackendKind, Manifest};
+use game_of_life::pattern::Pattern;
+use game_of_life::period::PeriodDetector;
+use game_of_life::render::{Image, RenderMode};
+use game_of_life::sparse::SparseField;
+use game_of_life::stats::Stats;
+use game_of_life::Field;
+
+const DEFAULT_GENERATIONS: u64 = 1000;   // 進める世代数の初期値（--generations オプションで変更できる）
+const DEFAULT_COL_N: u32 = 128;          // フィールドの列数の初期値（--size オプションで変更できる）
+const DEFAULT_ROW_N: u32 = 72;           // フィールドの行数の初期値
+const DEFAULT_ALIVE_RATIO: f32 = 0.25;   // フィールド生成時に生存しているセルの割合の初期値
+const DEFAULT_CELL_SIZE: u32 = 4;        // 画像のセル一つあたりの1辺の長さ（ピクセル）の初期値
+const DEFAULT_OUTPUT: &str = "headless"; // 書き出すファイルの名前（拡張子を除く）の初期値
+const MAX_DETECTED_PERIOD: usize = 64;   // フィールドが落ち着いたと判定する周期の上限（世代数）
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let generations = parse_arg("--generations")?.unwrap_or(DEFAULT_GENERATIONS);
+    let cell_size = parse_arg("--cell-size")?.unwrap_or(DEFAULT_CELL_SIZE);
+    let output = arg_value("--output").unwrap_or_else(|| DEFAULT_OUTPUT.to_string());
+
+    // --manifest オプションがあればその内容から、なければ他のオプションからフィールドの作り方を決める
+    let mut manifest = match arg_value("--manifest") {
+        Some(path) => std::fs::read_to_string(&path)
+            .map_err(|err| format!("failed to read {}: {}", path, err))?
+            .parse::<Manifest>()?,
+        None => {
+            let (col_n, row_n) = match arg_value("--size") {
+                Some(size) => parse_size(&size)?,
+                None => (DEFAULT_COL_N, DEFAULT_ROW_N),
+            };
+            Manifest {
+                seed: parse_arg("--seed")?.unwrap_or_else(rand::random),
+                rule: parse_arg("--rule")?.unwrap_or_default(),
+                boundary: parse_arg("--boundary")?.unwrap_or_default(),
+                col_n,
+                row_n,
+                alive_ratio: parse_arg("--alive-ratio")?.unwrap_or(DEFAULT_ALIVE_RATIO),
+                generation: 0,
+                backend: parse_arg("--backend")?.unwrap_or_default(),
+            }
+        },
+    };
+    let mut field = manifest.replay(parse_arg("--colours")?.unwrap_or_default());
+    field.threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    // --pattern オプションがあれば、生成したセルを消してパターンだけを中央に置く
+    if let Some(path) = arg_value("--pattern") {
+        let pattern = Pattern::load(&path)?;
+        field.clear();
+        field.place_pattern_centered(&pattern);
+        if let (Some(rule), None) = (pattern.rule, arg_value("--rule")) {
+            field.rule = rule;
+            manifest.rule = rule;
+        }
+        println!("# pattern = {}", path);
+    }
+    manifest.check()?;
+    print!("{}", manifest);
+    // 続きの世代も、マニフェストを再現したのと同じ計算方法で進める
+    let mut backend = Backend::new(manifest.backend, &field);
+
+    let start = manifest.generation;
+    let mut stats = Stats::new(start, alive_positions(&field));
+    let mut detector = PeriodDetector::new(MAX_DETECTED_PERIOD);
+    detector.observe(&field);
+    let mut settled = None;
+    for generation in start + 1..=start + generations {
+        backend.iterate(&mut field);
+        stats.record(generation, alive_positions(&field));
+        if settled.is_none() {
+            settled = detector.observe(&field).map(|outcome| (generation, outcome));
+        }
+    }
+
+    let mut summary = stats.summary().to_string();
+    match settled {
+        Some((generation, outcome)) => summary += &format!("settled = {} (generation {})\n", outcome, generation),
+        None => summary += "settled = no\n",
+    }
+    print!("{}", summary);
+
+    write(&format!("{}.rle", output), &field.to_pattern().to_rle())?;
+    write(&format!("{}.stats", output), &summary)?;
+    let png_path = format!("{}.png", output);
+    let render_mode: RenderMode = parse_arg("--render")?.unwrap_or_default();
+    Image::render(&field, cell_size, render_mode).save_png(&png_path)?;
+    println!("saved {}.rle, {}, {}.stats", output, png_path, output);
+    Ok(())
+}
+
+// フィールドの世代を進めるためのバックエンド
+// Dense以外では、バックエンドが持つ状態で計算し、結果を毎世代フィールドに書き戻す
+enum Backend {
+    Dense,               // Field::iterate で1世代ずつ進める
+    BitPacked(BitField), // 1セル1ビットのグリッドで1世代ずつ進める
+    HashLife(HashLife),  // 無限平面上のHashLifeで1世代ずつ進める
+    Sparse(SparseField), // 端のない平面で1世代ずつ進める。フィールドの外に出たセルも計算し続ける
+}
+
+impl Backend {
+    // フィールドの今の状態から、kindの計算方法のバックエンドを作る
+    fn new(kind: BackendKind, field: &Field) -> Backend {
+        match kind {
+            BackendKind::Dense => Backend::Dense,
+            BackendKind::BitPacked => Backend::BitPacked(BitField::from_field(field)),
+            BackendKind::HashLife => Backend::HashLife(HashLife::from_field(field)),
+            BackendKind::Sparse => Backend::Sparse(SparseField::from_field(field)),
+        }
+    }
+
+    // 1世代進め、フィールドの範囲に入っているセルをフィールドに書き戻す
+    fn iterate(&mut self, field: &mut Field) {
+        match self {
+            Backend::Dense => field.iterate(),
+            Backend::BitPacked(bitfield) => {
+                bitfield.iterate();
+                bitfield.write_to(field);
+            },
+            Backend::HashLife(hashlife) => {
+                hashlife.step(0);
+                hashlife.write_to(field);
+            },
+            Backend::Sparse(sparse) => {
+                sparse.iterate();
+                sparse.write_to(field);
+            },
+        }
+    }
+}
+
+// フィールドの生きているセル（Wireworldでは電子の頭）の座標を返す関数
+fn alive_positions(field: &Field) -> impl Iterator<Item = (i64, i64)> + '_ {
+    field.matrix.iter().enumerate().flat_map(|(x, col)| {
+        col.iter().enumerate()
+            .filter(|(_, cell)| field.rule.counts(cell.state))
+            .map(move |(y, _)| (x as i64, y as i64))
+    })
+}
+
+// "<columns>x<rows>" 形式のフィールドの大きさを読み込む関数
+fn parse_size(size: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("invalid --size {:?}: expected <columns>x<rows>", size);
+    let (col_n, row_n) = size.split_once('x').ok_or_else(invalid)?;
+    match (col_n.trim().parse(), row_n.trim().parse()) {
+        (Ok(col_n), Ok(row_n)) if col_n > 0 && row_n > 0 => Ok((col_n, row_n)),
+        _ => Err(invalid()),
+    }
+}
+
+// 文字列をファイルに書き出す関数
+fn write(path: &str, contents: &str) -> Result<(), String> {
+    std::fs::write(path, contents).map_err(|err| format!("failed to save {}: {}", path, err))
+}
@@ -0,0 +1,228 @@
+use crate::boundary::Boundary;
+use crate::neighbourhood::Neighbourhood;
+use crate::rule::{Counts, Rule};
+use crate::{CellState, Field};
+
+// 1セルを1ビットで表し、64セルずつまとめて次の世代を計算するフィールド
+// 各行を u64 の列に詰め、x列目のセルは (x / 64) 番目のワードの (x % 64) ビット目に置く
+pub struct BitField {
+    pub col_n: u32,         // フィールドの列数
+    pub row_n: u32,         // フィールドの行数
+    words_per_row: usize,   // 1行あたりのワード数
+    cells: Vec<u64>,        // 現在の世代（行ごとに words_per_row 個ずつ並べる）
+    next: Vec<u64>,         // 次の世代を書き込むためのバッファ
+    empty: Vec<u64>,        // Deadの境界で領域外の行として使う、すべて0の1行
+    birth: [u64; 9],        // 接触数ごとに、誕生するなら全ビットが1のマスク
+    survival: [u64; 9],     // 接触数ごとに、生存するなら全ビットが1のマスク
+    pub boundary: Boundary, // DeadかTorusのみ
+}
+
+impl BitField {
+    // ビット演算で計算できるルールと境界かどうか
+    // 2状態・半径1のMoore近傍で、接触数が列挙されているルールと、DeadかTorusの境界に対応する
+    pub fn supports(rule: &Rule, boundary: Boundary) -> bool {
+        rule.states == 2
+            && rule.neighbourhood == Neighbourhood::default()
+            && matches!((rule.birth, rule.survival), (Counts::List(_), Counts::List(_)))
+            && matches!(boundary, Boundary::Dead | Boundary::Torus)
+    }
+
+    // フィールドの生きたセルとルール・境界を写し取るためのメソッド
+    pub fn from_field(field: &Field) -> BitField {
+        let words_per_row = (field.col_n as usize).div_ceil(64);
+        let mask = |counts: &Counts| -> [u64; 9] {
+            let mut masks = [0u64; 9];
+            for (n, mask) in masks.iter_mut().enumerate() {
+                if counts.contains(n as u32) {
+                    *mask = !0;
+                }
+            }
+            masks
+        };
+        let mut bitfield = BitField {
+            col_n: field.col_n,
+            row_n: field.row_n,
+            words_per_row,
+            cells: vec![0; words_per_row * field.row_n as usize],
+            next: vec![0; words_per_row * field.row_n as usize],
+            empty: vec![0; words_per_row],
+            birth: mask(&field.rule.birth),
+            survival: mask(&field.rule.survival),
+            boundary: field.boundary,
+        };
+        for x in 0..field.col_n as usize {
+            for y in 0..field.row_n as usize {
+                if field.get_cell(x, y).state == CellState::Alive {
+                    bitfield.cells[y * words_per_row + x / 64] |= 1 << (x % 64);
+                }
+            }
+        }
+        bitfield
+    }
+
+    // 現在の世代をフィールドに書き戻すためのメソッド（セルの色はフィールドのものをそのまま使う）
+    pub fn write_to(&self, field: &mut Field) {
+        for x in 0..self.col_n as usize {
+            for y in 0..self.row_n as usize {
+                let cell = &mut field.matrix[x][y];
+                *cell = cell.next_clone(if self.get(x, y) { CellState::Alive } else { CellState::Dead });
+            }
+        }
+    }
+
+    // (x, y)のセルが生きているかどうか
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.cells[y * self.words_per_row + x / 64] >> (x % 64) & 1 == 1
+    }
+
+    // 生きているセルの数
+    pub fn population(&self) -> u64 {
+        self.cells.iter().map(|word| word.count_ones() as u64).sum()
+    }
+
+    // フィールドを次の状態に移行するためのメソッド
+    pub fn iterate(&mut self) {
+        let (w, h) = (self.words_per_row, self.row_n as usize);
+        // 最後のワードのうち、フィールドに含まれる列のビットだけが1のマスク
+        let last_mask = match self.col_n % 64 {
+            0 => !0,
+            bits => (1u64 << bits) - 1,
+        };
+
+        for y in 0..h {
+            // 上下の行（Deadなら領域外は空の行、Torusなら反対側の行）
+            let row = |y: usize| &self.cells[y * w..(y + 1) * w];
+            let (up, down) = match self.boundary {
+                Boundary::Torus => (row((y + h - 1) % h), row((y + 1) % h)),
+                _ => (
+                    if y == 0 { &self.empty[..] } else { row(y - 1) },
+                    if y + 1 == h { &self.empty[..] } else { row(y + 1) },
+                ),
+            };
+            let mid = row(y);
+
+            for i in 0..w {
+                // 周囲8セルの生死を、ビットごとに独立した4ビットの数（sums[0]が最下位）に足し合わせる
+                let mut sums = [0u64; 4];
+                for (line, center) in [(up, true), (mid, false), (down, true)] {
+                    let (west, east) = self.shifted(line, i);
+                    add(&mut sums, west);
+                    add(&mut sums, east);
+                    // 同じ行の真ん中は自分自身なので数えない
+                    if center {
+                        add(&mut sums, line[i]);
+                    }
+                }
+
+                // 接触数ごとに一致するビットを求め、ルールに応じて誕生・生存するビットを集める
+                let (mut born, mut survive) = (0u64, 0u64);
+                for n in 0..9 {
+                    let mut equal = !0u64;
+                    for (bit, sum) in sums.iter().enumerate() {
+                        equal &= if n >> bit & 1 == 1 { *sum } else { !*sum };
+                    }
+                    born |= equal & self.birth[n];
+                    survive |= equal & self.survival[n];
+                }
+                let alive = mid[i];
+                let mut next = (alive & survive) | (!alive & born);
+                if i + 1 == w {
+                    next &= last_mask;
+                }
+                self.next[y * w + i] = next;
+            }
+        }
+        std::mem::swap(&mut self.cells, &mut self.next);
+    }
+
+    // 行のi番目のワードについて、各ビットに左隣（west）・右隣（east）のセルを並べたワードを返す
+    fn shifted(&self, line: &[u64], i: usize) -> (u64, u64) {
+        let w = line.len();
+        let torus = self.boundary == Boundary::Torus;
+        let mut west = line[i] << 1;
+        if 0 < i {
+            west |= line[i - 1] >> 63;
+        } else if torus {
+            let x = self.col_n as usize - 1;
+            west |= line[x / 64] >> (x % 64) & 1;
+        }
+        let mut east = line[i] >> 1;
+        if i + 1 < w {
+            east |= line[i + 1] << 63;
+        } else if torus {
+            east |= (line[0] & 1) << ((self.col_n as usize - 1) % 64);
+        }
+        (west, east)
+    }
+}
+
+// ビットごとの4ビットの数sumsに、valueの各ビット（0か1）を加える
+fn add(sums: &mut [u64; 4], value: u64) {
+    let mut carry = value;
+    for sum in sums.iter_mut() {
+        let next_carry = *sum & carry;
+        *sum ^= carry;
+        carry = next_carry;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    // 乱数で埋めたフィールドを作る
+    fn soup_field(col_n: u32, row_n: u32, rule: Rule, boundary: Boundary, seed: u64) -> Field {
+        let mut field = Field::new(row_n, col_n, 0.0);
+        field.rule = rule;
+        field.boundary = boundary;
+        let mut rng = StdRng::seed_from_u64(seed);
+        for cell in field.matrix.iter_mut().flatten() {
+            if rng.gen_bool(0.35) {
+                cell.state = CellState::Alive;
+            }
+        }
+        field
+    }
+
+    fn states(field: &Field) -> Vec<Vec<CellState>> {
+        field.matrix.iter().map(|col| col.iter().map(|cell| cell.state).collect()).collect()
+    }
+
+    #[test]
+    fn matches_the_dense_field() {
+        // 64の倍数ちょうど・半端・1ワード未満の幅で、端の処理を確かめる
+        for (col_n, row_n) in [(128, 40), (100, 33), (23, 17)] {
+            for boundary in [Boundary::Dead, Boundary::Torus] {
+                for rule in ["B3/S23", "B36/S23", "B3678/S34678", "B2/S"] {
+                    let rule: Rule = rule.parse().unwrap();
+                    let mut field = soup_field(col_n, row_n, rule, boundary, col_n as u64);
+                    let mut bitfield = BitField::from_field(&field);
+                    let mut copy = soup_field(col_n, row_n, rule, boundary, col_n as u64);
+                    for generation in 1..=40 {
+                        field.iterate();
+                        bitfield.iterate();
+                        bitfield.write_to(&mut copy);
+                        assert_eq!(states(&copy), states(&field),
+                            "{} {} {}x{} generation {}", rule, boundary, col_n, row_n, generation);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn counts_population() {
+        let field = soup_field(70, 10, Rule::conway(), Boundary::Dead, 1);
+        let alive = field.matrix.iter().flatten().filter(|cell| cell.state == CellState::Alive).count();
+        assert_eq!(BitField::from_field(&field).population(), alive as u64);
+    }
+
+    #[test]
+    fn rejects_unsupported_rules_and_boundaries() {
+        assert!(BitField::supports(&Rule::conway(), Boundary::Torus));
+        assert!(!BitField::supports(&Rule::conway(), Boundary::Mirror));
+        assert!(!BitField::supports(&"B2/S/C3".parse().unwrap(), Boundary::Dead));
+        assert!(!BitField::supports(&"B2/S34H".parse().unwrap(), Boundary::Dead));
+    }
+}
@@ -0,0 +1,183 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::neighbourhood::Neighbourhood;
+
+// フィールドの端の扱い（位相）を表現するための列挙型
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Boundary {
+    #[default]
+    Dead,            // 領域外は常に死んでいるものとみなす
+    Torus,           // 上下・左右の端がそれぞれつながっている
+    KleinBottle,     // 左右はそのままつながり、上下は左右を反転してつながる
+    ProjectivePlane, // 上下・左右ともに反転してつながる
+    Mirror,          // 端で鏡のように反射する
+}
+
+// Bキーで切り替える順番
+pub const ALL: [Boundary; 5] = [
+    Boundary::Dead,
+    Boundary::Torus,
+    Boundary::KleinBottle,
+    Boundary::ProjectivePlane,
+    Boundary::Mirror,
+];
+
+impl Boundary {
+    // 領域外を含む座標(x, y)を、境界の種類に応じてフィールド内の座標に変換するためのメソッド
+    // 対応するセルが無い（Deadで領域外の）場合はNoneを返す
+    pub fn resolve(&self, x: i32, y: i32, col_n: u32, row_n: u32) -> Option<(usize, usize)> {
+        let (w, h) = (col_n as i32, row_n as i32);
+        let (x, y) = match self {
+            Boundary::Dead => {
+                if x < 0 || w <= x || y < 0 || h <= y {
+                    return None;
+                }
+                (x, y)
+            },
+            Boundary::Torus => (x.rem_euclid(w), y.rem_euclid(h)),
+            Boundary::KleinBottle => {
+                // 上下の端を奇数回またいだら左右が反転する
+                let x = x.rem_euclid(w);
+                let x = if y.div_euclid(h) % 2 != 0 { w - 1 - x } else { x };
+                (x, y.rem_euclid(h))
+            },
+            Boundary::ProjectivePlane => {
+                // 左右の端をまたぐと上下が、上下の端をまたぐと左右が反転する
+                let y = if x.div_euclid(w) % 2 != 0 { h - 1 - y } else { y };
+                let x = x.rem_euclid(w);
+                let x = if y.div_euclid(h) % 2 != 0 { w - 1 - x } else { x };
+                (x, y.rem_euclid(h))
+            },
+            Boundary::Mirror => (reflect(x, w), reflect(y, h)),
+        };
+        Some((x as usize, y as usize))
+    }
+
+    // 近傍neighbourhoodをrow_n行のフィールドでこの境界と組み合わせて使えるか確かめるメソッド
+    // 六角形グリッドは行の偶奇で近傍のずれ方が変わるため、上下の端をまたいだときに偶奇の扱いが食い違ってはいけない
+    // トーラスでは行数が偶数、上下で左右が反転するクラインの壺と射影平面では（反転とずれの向きが打ち消し合うよう）奇数でなければならない
+    pub fn check(&self, neighbourhood: Neighbourhood, row_n: u32) -> Result<(), String> {
+        let even = match (neighbourhood, self) {
+            (Neighbourhood::Hexagonal, Boundary::Torus) => true,
+            (Neighbourhood::Hexagonal, Boundary::KleinBottle | Boundary::ProjectivePlane) => false,
+            _ => return Ok(()),
+        };
+        if row_n.is_multiple_of(2) == even {
+            return Ok(());
+        }
+        Err(format!("a hexagonal grid with {} boundary needs an {} number of rows, not {}",
+            self, if even { "even" } else { "odd" }, row_n))
+    }
+
+    // ALLの中で次の境界を返すメソッド
+    pub fn next(&self) -> Boundary {
+        let i = ALL.iter().position(|b| b == self).unwrap();
+        ALL[(i + 1) % ALL.len()]
+    }
+}
+
+// 長さnの区間の端で反射させた座標を返す（-1は0に、nはn-1に対応する）
+fn reflect(v: i32, n: i32) -> i32 {
+    let m = v.rem_euclid(2 * n);
+    if m < n { m } else { 2 * n - 1 - m }
+}
+
+impl fmt::Display for Boundary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Boundary::Dead => "dead",
+            Boundary::Torus => "torus",
+            Boundary::KleinBottle => "klein",
+            Boundary::ProjectivePlane => "projective",
+            Boundary::Mirror => "mirror",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Boundary {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Boundary, String> {
+        ALL.iter()
+            .find(|b| b.to_string().eq_ignore_ascii_case(s.trim()))
+            .copied()
+            .ok_or_else(|| format!("unknown boundary {:?}: expected one of dead, torus, klein, projective, mirror", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dead_skips_outside_cells() {
+        assert_eq!(Boundary::Dead.resolve(0, 0, 4, 3), Some((0, 0)));
+        assert_eq!(Boundary::Dead.resolve(-1, 0, 4, 3), None);
+        assert_eq!(Boundary::Dead.resolve(3, 3, 4, 3), None);
+    }
+
+    #[test]
+    fn torus_wraps_both_axes() {
+        assert_eq!(Boundary::Torus.resolve(-1, -1, 4, 3), Some((3, 2)));
+        assert_eq!(Boundary::Torus.resolve(4, 3, 4, 3), Some((0, 0)));
+    }
+
+    #[test]
+    fn klein_bottle_flips_across_top_and_bottom() {
+        assert_eq!(Boundary::KleinBottle.resolve(-1, 1, 4, 3), Some((3, 1)));
+        assert_eq!(Boundary::KleinBottle.resolve(0, -1, 4, 3), Some((3, 2)));
+        assert_eq!(Boundary::KleinBottle.resolve(1, 3, 4, 3), Some((2, 0)));
+        assert_eq!(Boundary::KleinBottle.resolve(1, 6, 4, 3), Some((1, 0)));
+    }
+
+    #[test]
+    fn projective_plane_flips_across_every_edge() {
+        assert_eq!(Boundary::ProjectivePlane.resolve(-1, 0, 4, 3), Some((3, 2)));
+        assert_eq!(Boundary::ProjectivePlane.resolve(4, 1, 4, 3), Some((0, 1)));
+        assert_eq!(Boundary::ProjectivePlane.resolve(0, 3, 4, 3), Some((3, 0)));
+    }
+
+    #[test]
+    fn mirror_reflects_at_the_edges() {
+        assert_eq!(Boundary::Mirror.resolve(-1, -1, 4, 3), Some((0, 0)));
+        assert_eq!(Boundary::Mirror.resolve(4, 3, 4, 3), Some((3, 2)));
+        assert_eq!(Boundary::Mirror.resolve(-2, 4, 4, 3), Some((1, 1)));
+    }
+
+    // 近傍のセルが互いに相手を近傍として数えるか（接触関係が対称か）を返す
+    fn symmetric(boundary: Boundary, neighbourhood: Neighbourhood, col_n: u32, row_n: u32) -> bool {
+        let neighbours = |x: usize, y: usize| -> Vec<(usize, usize)> {
+            neighbourhood.offsets(y).into_iter()
+                .filter_map(|(dx, dy)| boundary.resolve(x as i32 + dx, y as i32 + dy, col_n, row_n))
+                .collect()
+        };
+        (0..col_n as usize).all(|x| (0..row_n as usize).all(|y| {
+            neighbours(x, y).into_iter().all(|(nx, ny)| neighbours(nx, ny).contains(&(x, y)))
+        }))
+    }
+
+    #[test]
+    fn hexagonal_grids_accept_only_row_counts_that_keep_neighbours_symmetric() {
+        for boundary in ALL {
+            for row_n in 5..=8 {
+                let accepted = boundary.check(Neighbourhood::Hexagonal, row_n).is_ok();
+                assert_eq!(accepted, symmetric(boundary, Neighbourhood::Hexagonal, 7, row_n), "{} with {} rows", boundary, row_n);
+                assert!(boundary.check(Neighbourhood::default(), row_n).is_ok());
+            }
+        }
+        assert!(Boundary::Torus.check(Neighbourhood::Hexagonal, 7).is_err());
+        assert!(Boundary::KleinBottle.check(Neighbourhood::Hexagonal, 6).is_err());
+    }
+
+    #[test]
+    fn parses_and_cycles_names() {
+        for boundary in ALL {
+            assert_eq!(boundary.to_string().parse::<Boundary>().unwrap(), boundary);
+        }
+        assert_eq!("Torus".parse::<Boundary>().unwrap(), Boundary::Torus);
+        assert!("sphere".parse::<Boundary>().is_err());
+        assert_eq!(Boundary::Mirror.next(), Boundary::Dead);
+    }
+}
@@ -0,0 +1,471 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::ops::Range;
+
+use crate::neighbourhood::Neighbourhood;
+use crate::rule::Rule;
+use crate::sparse::SparseField;
+use crate::{CellState, Field};
+
+// ランダムなスープを落ち着くまで進め、残った灰を物体に分けて数える（apgsearchと同じ考え方の調査）
+// 物体は Catagolue と同じ apgcode（"xs4_33" など）で表すため、他の調査結果と比べられる
+
+pub const SOUP_SIZE: u32 = 16;                    // スープ（ランダムに生成する正方形の領域）の1辺のセル数
+pub const SOUP_ALIVE_RATIO: f32 = 0.5;            // スープで生存しているセルの割合
+pub const MAX_GENERATIONS: usize = 20000;         // これだけ進めても落ち着かないスープは、落ち着かなかったものとして記録する
+pub const UNIDENTIFIED: &str = "zz_UNIDENTIFIED"; // 単独で進めても周期的にならず、分類できなかった物体
+const MAX_PERIOD: usize = 30;                     // 物体の周期として調べる上限（世代数）
+const STABLE_WINDOW: usize = 4 * MAX_PERIOD;      // 生存セルの数がこの世代数の間周期的であれば、スープが落ち着いたとみなす
+const MERGE_GENERATIONS: usize = 8;               // 物体に分けるときに、生存セルを重ね合わせる世代数
+const SAMPLE_SEEDS: usize = 5;                    // 物体ごとに記録する、その物体が現れたスープの種の数
+
+// apgcodeの1文字が表す値（0〜35）
+const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+// よく現れる物体の apgcode と名前
+pub const NAMES: [(&str, &str); 15] = [
+    ("xs4_33", "block"),
+    ("xs4_252", "tub"),
+    ("xs5_253", "boat"),
+    ("xs6_696", "beehive"),
+    ("xs6_356", "ship"),
+    ("xs6_25a4", "barge"),
+    ("xs7_2596", "loaf"),
+    ("xs7_25ac", "long boat"),
+    ("xs8_6996", "pond"),
+    ("xp2_7", "blinker"),
+    ("xp2_7e", "toad"),
+    ("xp2_318c", "beacon"),
+    ("xp15_4r4z4r4", "pentadecathlon"),
+    ("xq4_153", "glider"),
+    ("xq4_6frc", "lightweight spaceship"),
+];
+
+// apgcodeに対応する名前を返す関数。名前の付いていない物体ならNoneを返す
+pub fn name(apgcode: &str) -> Option<&'static str> {
+    NAMES.iter().find(|(code, _)| *code == apgcode).map(|&(_, name)| name)
+}
+
+// この調査で扱えるルールかどうか
+// 端のないフィールドで進められ、死につつある状態がなく、回転・反転しても同じ近傍を持つルールだけを扱う
+pub fn supports(rule: &Rule) -> bool {
+    SparseField::supports(rule) && rule.states == 2 && rule.neighbourhood != Neighbourhood::Hexagonal
+}
+
+// 種seedのスープを、SOUP_SIZE四方のフィールドとして作る関数
+pub fn soup(seed: u64, rule: Rule) -> Field {
+    let mut field = Field::with_seed(SOUP_SIZE, SOUP_SIZE, SOUP_ALIVE_RATIO, seed);
+    field.rule = rule;
+    field
+}
+
+// 種seedのスープを端のないフィールドで落ち着くまで進め、残った物体のapgcodeを返す関数
+// スープは端のない平面に置いて進めるため、再現するときも端のない平面で進める（headless の --backend sparse）
+// MAX_GENERATIONS世代進めても落ち着かなければNoneを返す
+pub fn search_soup(seed: u64, rule: Rule) -> Option<Vec<String>> {
+    let mut sparse = SparseField::from_field(&soup(seed, rule));
+    let mut populations = vec![sparse.population()];
+    while !settled(&populations) {
+        if MAX_GENERATIONS < populations.len() {
+            return None;
+        }
+        sparse.iterate();
+        populations.push(sparse.population());
+    }
+    Some(split_objects(&sparse).iter().flat_map(|cells| identify(cells, rule)).collect())
+}
+
+// 直近STABLE_WINDOW世代の生存セルの数が、MAX_PERIOD以下のどれかの周期で繰り返していればtrueを返す関数
+// 飛び去っていくグライダーがあるとフィールド全体は同じ状態に戻らないため、セルの数の周期で判定する
+fn settled(populations: &[u64]) -> bool {
+    if populations.len() < STABLE_WINDOW {
+        return false;
+    }
+    let recent = &populations[populations.len() - STABLE_WINDOW..];
+    (1..=MAX_PERIOD).any(|period| (period..recent.len()).all(|i| recent[i] == recent[i - period]))
+}
+
+// 落ち着いたフィールドの生存セルを、物体ごとの座標のリストに分ける関数
+// 振動子は位相によって離れたセルを持つことがあるため、数世代分の生存セルを重ね合わせてつながっているものを1つの物体とする
+// 斜めに接している2つの静物などは、apgsearchと違って1つの物体として数える
+pub fn split_objects(sparse: &SparseField) -> Vec<Vec<(i64, i64)>> {
+    let current = alive_cells(sparse);
+    let mut union: HashSet<(i64, i64)> = current.iter().copied().collect();
+    let mut future = SparseField::new(sparse.rule);
+    future.cells = sparse.cells.clone();
+    for _ in 0..MERGE_GENERATIONS {
+        future.iterate();
+        union.extend(alive_cells(&future));
+    }
+    components(&union).iter()
+        .map(|component| current.iter().copied().filter(|position| component.contains(position)).collect::<Vec<_>>())
+        .filter(|cells| !cells.is_empty())
+        .collect()
+}
+
+// 物体を分類したapgcodeを返す関数
+// 単独で進めると分類できない場合（近くの物体と影響し合っている場合など）は、隣接しているセルごとに分けてから分類し直す
+fn identify(cells: &[(i64, i64)], rule: Rule) -> Vec<String> {
+    if let Some(apgcode) = classify(cells, rule) {
+        return vec![apgcode];
+    }
+    let pieces = components(&cells.iter().copied().collect());
+    if pieces.len() == 1 {
+        return vec![UNIDENTIFIED.to_string()];
+    }
+    pieces.iter()
+        .map(|piece| classify(&piece.iter().copied().collect::<Vec<_>>(), rule).unwrap_or_else(|| UNIDENTIFIED.to_string()))
+        .collect()
+}
+
+// 生存セルcellsだけを置いた端のないフィールドを進め、周期と移動からapgcodeを求める関数
+// 静物は "xs<セルの数>"、振動子は "xp<周期>"、宇宙船は "xq<周期>" に続けて、
+// すべての位相と向きのうち最も短い（同じ長さなら辞書順で最も小さい）拡張Wechsler形式の文字列を書く
+// MAX_PERIOD世代以内に同じ形に戻らなければNoneを返す
+pub fn classify(cells: &[(i64, i64)], rule: Rule) -> Option<String> {
+    let mut sparse = SparseField::new(rule);
+    for &position in cells {
+        sparse.set(position, true);
+    }
+    let (start, origin) = normalise(cells);
+    let mut phases = vec![start.clone()];
+    for period in 1..=MAX_PERIOD {
+        sparse.iterate();
+        let (phase, corner) = normalise(&alive_cells(&sparse));
+        if phase.is_empty() {
+            return None;
+        }
+        if phase == start {
+            let prefix = match (period, corner == origin) {
+                (1, true) => format!("xs{}", start.len()),
+                (_, true) => format!("xp{}", period),
+                (_, false) => format!("xq{}", period),
+            };
+            let code = phases.iter()
+                .flat_map(|phase| orientations(phase))
+                .map(|cells| wechsler(&cells))
+                .min_by(|a, b| (a.len(), a).cmp(&(b.len(), b)))
+                .unwrap();
+            return Some(format!("{}_{}", prefix, code));
+        }
+        phases.push(phase);
+    }
+    None
+}
+
+// フィールドの生存セルの座標を返す関数
+fn alive_cells(sparse: &SparseField) -> Vec<(i64, i64)> {
+    sparse.cells.iter()
+        .filter(|(_, cell)| cell.state == CellState::Alive)
+        .map(|(&position, _)| position)
+        .collect()
+}
+
+// 座標の集合を、周囲8セルでつながっている部分ごとに分ける関数
+fn components(cells: &HashSet<(i64, i64)>) -> Vec<HashSet<(i64, i64)>> {
+    let mut remaining = cells.clone();
+    let mut components = vec![];
+    // 結果の順番が毎回同じになるよう、座標の順に探し始める
+    let mut starts: Vec<(i64, i64)> = cells.iter().copied().collect();
+    starts.sort();
+    for start in starts {
+        if !remaining.remove(&start) {
+            continue;
+        }
+        let mut component = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some((x, y)) = queue.pop_front() {
+            for offset_x in -1..=1 {
+                for offset_y in -1..=1 {
+                    let position = (x + offset_x, y + offset_y);
+                    if remaining.remove(&position) {
+                        component.insert(position);
+                        queue.push_back(position);
+                    }
+                }
+            }
+        }
+        components.push(component);
+    }
+    components
+}
+
+// 座標を左上が(0, 0)になるよう平行移動して並べ替えたものと、元の左上の座標を返す関数
+fn normalise(cells: &[(i64, i64)]) -> (Vec<(i64, i64)>, (i64, i64)) {
+    let min_x = cells.iter().map(|&(x, _)| x).min().unwrap_or(0);
+    let min_y = cells.iter().map(|&(_, y)| y).min().unwrap_or(0);
+    let mut normalised: Vec<(i64, i64)> = cells.iter().map(|&(x, y)| (x - min_x, y - min_y)).collect();
+    normalised.sort();
+    (normalised, (min_x, min_y))
+}
+
+// 座標の回転・反転
+type Transform = fn((i64, i64)) -> (i64, i64);
+
+// 回転・反転した8通りの向きの座標を返す関数
+fn orientations(cells: &[(i64, i64)]) -> Vec<Vec<(i64, i64)>> {
+    let transforms: [Transform; 8] = [
+        |(x, y)| (x, y),
+        |(x, y)| (-x, y),
+        |(x, y)| (x, -y),
+        |(x, y)| (-x, -y),
+        |(x, y)| (y, x),
+        |(x, y)| (-y, x),
+        |(x, y)| (y, -x),
+        |(x, y)| (-y, -x),
+    ];
+    transforms.iter()
+        .map(|transform| normalise(&cells.iter().map(|&position| transform(position)).collect::<Vec<_>>()).0)
+        .collect()
+}
+
+// 左上が(0, 0)の座標を拡張Wechsler形式で書く関数
+// 5行ずつの帯に分け、帯の各列を上の行が最下位ビットの5ビットの値（0〜v）で書き、帯の間は "z" で区切る
+// 空の列の並びは "0"（1列）、"w"（2列）、"x"（3列）、"y" と1文字（4〜39列）に縮め、帯の末尾の空の列は書かない
+fn wechsler(cells: &[(i64, i64)]) -> String {
+    let width = cells.iter().map(|&(x, _)| x + 1).max().unwrap_or(0);
+    let height = cells.iter().map(|&(_, y)| y + 1).max().unwrap_or(0);
+    let mut strips = vec![];
+    for top in (0..height).step_by(5) {
+        let mut columns = vec![0usize; width as usize];
+        for &(x, y) in cells.iter().filter(|&&(_, y)| top <= y && y < top + 5) {
+            columns[x as usize] |= 1 << (y - top);
+        }
+        while columns.last() == Some(&0) {
+            columns.pop();
+        }
+        let mut strip = String::new();
+        let mut zeros = 0;
+        for column in columns {
+            if column == 0 {
+                zeros += 1;
+                continue;
+            }
+            strip += &zero_run(zeros);
+            zeros = 0;
+            strip.push(DIGITS[column] as char);
+        }
+        strips.push(strip);
+    }
+    strips.join("z")
+}
+
+// zeros列の空の列の並びを書く関数
+fn zero_run(mut zeros: usize) -> String {
+    let mut run = String::new();
+    while 4 <= zeros {
+        let n = zeros.min(39);
+        run.push('y');
+        run.push(DIGITS[n - 4] as char);
+        zeros -= n;
+    }
+    run + ["", "0", "w", "x"][zeros]
+}
+
+// 物体1種類の集計
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Tally {
+    pub count: u64,      // 現れた数
+    pub seeds: Vec<u64>, // その物体が現れたスープの種（小さい順にSAMPLE_SEEDS個まで）
+}
+
+// たくさんのスープから集めた物体の集計表
+pub struct Census {
+    pub rule: Rule,                      // スープを進めたルール
+    pub seeds: Option<(u64, u64)>,       // 調べたスープの種の最小値と最大値
+    pub soups: u64,                      // 調べたスープの数
+    pub unstabilised: Vec<u64>,          // 落ち着かなかったスープの種（小さい順）
+    pub objects: HashMap<String, Tally>, // apgcodeごとの集計
+}
+
+impl Census {
+    // 空の集計表を作るためのメソッド
+    pub fn new(rule: Rule) -> Census {
+        Census { rule, seeds: None, soups: 0, unstabilised: vec![], objects: HashMap::new() }
+    }
+
+    // 種seedsのスープをthreads個のスレッドで分担して調べ、集計表を作るためのメソッド
+    pub fn search(rule: Rule, seeds: Range<u64>, threads: usize) -> Census {
+        let threads = threads.max(1) as u64;
+        let band = seeds.end.saturating_sub(seeds.start).div_ceil(threads);
+        let censuses: Vec<Census> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|i| {
+                    let start = (seeds.start + i * band).min(seeds.end);
+                    let end = (start + band).min(seeds.end);
+                    scope.spawn(move || {
+                        let mut census = Census::new(rule);
+                        for seed in start..end {
+                            census.add(seed, search_soup(seed, rule));
+                        }
+                        census
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        let mut census = Census::new(rule);
+        for other in censuses {
+            census.merge(other);
+        }
+        census
+    }
+
+    // 種seedのスープの結果を集計に加えるためのメソッド（Noneは落ち着かなかったスープ）
+    pub fn add(&mut self, seed: u64, objects: Option<Vec<String>>) {
+        self.soups += 1;
+        self.seeds = Some(match self.seeds {
+            Some((first, last)) => (first.min(seed), last.max(seed)),
+            None => (seed, seed),
+        });
+        let Some(objects) = objects else {
+            self.unstabilised.push(seed);
+            self.unstabilised.sort();
+            return;
+        };
+        for apgcode in objects {
+            let tally = self.objects.entry(apgcode).or_default();
+            tally.count += 1;
+            if !tally.seeds.contains(&seed) {
+                tally.seeds.push(seed);
+                tally.seeds.sort();
+                tally.seeds.truncate(SAMPLE_SEEDS);
+            }
+        }
+    }
+
+    // 別の集計表の結果を合わせるためのメソッド
+    pub fn merge(&mut self, other: Census) {
+        self.soups += other.soups;
+        self.seeds = match (self.seeds, other.seeds) {
+            (Some((first, last)), Some((other_first, other_last))) => Some((first.min(other_first), last.max(other_last))),
+            (seeds, None) | (None, seeds) => seeds,
+        };
+        self.unstabilised.extend(other.unstabilised);
+        self.unstabilised.sort();
+        for (apgcode, other) in other.objects {
+            let tally = self.objects.entry(apgcode).or_default();
+            tally.count += other.count;
+            tally.seeds.extend(other.seeds);
+            tally.seeds.sort();
+            tally.seeds.dedup();
+            tally.seeds.truncate(SAMPLE_SEEDS);
+        }
+    }
+
+    // 多く現れた順（同数ならapgcodeの順）に並べた集計
+    pub fn sorted(&self) -> Vec<(&str, &Tally)> {
+        let mut sorted: Vec<(&str, &Tally)> = self.objects.iter().map(|(apgcode, tally)| (apgcode.as_str(), tally)).collect();
+        sorted.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+        sorted
+    }
+}
+
+// #で始まるコメント行に調査の条件を書き、その後に "apgcode,name,count,seeds" の列のCSVとして集計を書き出す
+// seeds の列には、その物体が現れたスープの種を空白区切りで書く
+impl fmt::Display for Census {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# game-of-life soup census")?;
+        writeln!(f, "# rule = {}", self.rule)?;
+        writeln!(f, "# soup = {}x{} at alive_ratio {}", SOUP_SIZE, SOUP_SIZE, SOUP_ALIVE_RATIO)?;
+        match self.seeds {
+            Some((first, last)) => writeln!(f, "# seeds = {} to {} ({} soups)", first, last, self.soups)?,
+            None => writeln!(f, "# seeds = none")?,
+        }
+        let unstabilised: Vec<String> = self.unstabilised.iter().map(|seed| seed.to_string()).collect();
+        writeln!(f, "# unstabilised = {} [{}]", unstabilised.len(), unstabilised.join(" "))?;
+        writeln!(f, "# objects = {}", self.objects.values().map(|tally| tally.count).sum::<u64>())?;
+        writeln!(f, "apgcode,name,count,seeds")?;
+        for (apgcode, tally) in self.sorted() {
+            let seeds: Vec<String> = tally.seeds.iter().map(|seed| seed.to_string()).collect();
+            writeln!(f, "{},{},{},{}", apgcode, name(apgcode).unwrap_or(""), tally.count, seeds.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::Pattern;
+
+    fn apgcode(rle: &str) -> Option<String> {
+        let pattern = Pattern::from_rle(&format!("x = 16, y = 16\n{}", rle)).unwrap();
+        let cells: Vec<(i64, i64)> = pattern.cells.iter().map(|&(x, y)| (x as i64, y as i64)).collect();
+        classify(&cells, Rule::conway())
+    }
+
+    #[test]
+    fn classifies_objects_with_catagolue_apgcodes() {
+        let objects = [
+            ("2o$2o!", "xs4_33"),
+            ("bo$obo$bo!", "xs4_252"),
+            ("2o$obo$bo!", "xs5_253"),
+            ("b2o$o2bo$b2o!", "xs6_696"),
+            ("b2o$o2bo$bobo$2bo!", "xs7_2596"),
+            ("b2o$o2bo$o2bo$b2o!", "xs8_6996"),
+            ("3o!", "xp2_7"),
+            ("b3o$3o!", "xp2_7e"),
+            ("2o$o$3bo$2b2o!", "xp2_318c"),
+            ("bo$2bo$3o!", "xq4_153"),
+            ("bo2bo$o$o3bo$4o!", "xq4_6frc"),
+            ("2bo4bo$2ob4ob2o$2bo4bo!", "xp15_4r4z4r4"),
+        ];
+        for (rle, expected) in objects {
+            assert_eq!(apgcode(rle).as_deref(), Some(expected), "{}", rle);
+            assert!(name(expected).is_some());
+        }
+        // MAX_PERIOD世代以内に周期的にならないR-ペントミノは分類できない
+        assert_eq!(apgcode("b2o$2o$bo!"), None);
+    }
+
+    #[test]
+    fn writes_runs_of_empty_columns_in_short_form() {
+        assert_eq!(wechsler(&[(0, 0), (2, 0)]), "101");
+        assert_eq!(wechsler(&[(0, 0), (3, 0), (7, 4)]), "1w1xg");
+        assert_eq!(wechsler(&[(0, 0), (7, 0), (40, 5)]), "1y21zyz01");
+    }
+
+    #[test]
+    fn splits_ash_into_objects() {
+        // 離れたブロックと瞬き、斜めに進むグライダー
+        let pattern = Pattern::from_rle("x = 22, y = 14\n2o$2o5$10b3o5$20bo$21bo$19b3o!").unwrap();
+        let mut sparse = SparseField::new(Rule::conway());
+        for &(x, y) in &pattern.cells {
+            sparse.set((x as i64, y as i64), true);
+        }
+        let mut apgcodes: Vec<String> = split_objects(&sparse).iter().flat_map(|cells| identify(cells, Rule::conway())).collect();
+        apgcodes.sort();
+        assert_eq!(apgcodes, ["xp2_7", "xq4_153", "xs4_33"]);
+    }
+
+    #[test]
+    fn soups_are_reproducible_and_counted() {
+        let census = Census::search(Rule::conway(), 0..4, 3);
+        assert_eq!(census.soups, 4);
+        assert_eq!(census.seeds, Some((0, 3)));
+        // スレッドの数を変えても、1つずつ調べても同じ集計になる
+        let mut serial = Census::new(Rule::conway());
+        for seed in 0..4 {
+            serial.add(seed, search_soup(seed, Rule::conway()));
+        }
+        assert_eq!(census.sorted(), serial.sorted());
+        assert_eq!(census.unstabilised, serial.unstabilised);
+        // 16x16のスープからはほぼ必ずブロックか瞬きが残る
+        assert!(census.objects.contains_key("xs4_33") || census.objects.contains_key("xp2_7"));
+
+        let text = census.to_string();
+        assert!(text.contains("# seeds = 0 to 3 (4 soups)"));
+        let rows = text.lines().skip_while(|line| *line != "apgcode,name,count,seeds").count() - 1;
+        assert_eq!(rows, census.objects.len());
+    }
+
+    #[test]
+    fn supports_only_symmetric_two_state_rules() {
+        assert!(supports(&Rule::conway()));
+        assert!(supports(&"B36/S23".parse().unwrap()));
+        assert!(!supports(&"B2/S/C3".parse().unwrap()));
+        assert!(!supports(&"B2/S34H".parse().unwrap()));
+        assert!(!supports(&Rule::wireworld()));
+    }
+}
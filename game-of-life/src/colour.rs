@@ -0,0 +1,158 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+use crate::Cell;
+
+// 新しく生まれたセルの色の決め方を表現するための列挙型
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ColourMode {
+    #[default]
+    Positional,  // その場所のセルにもともと割り当てられていた色のまま（色は位置で決まる）
+    Immigration, // 2色のパレットで塗り分け、親（接している生きたセル）の中で最も多い色を受け継ぐ
+    QuadLife,    // 4色のパレットで塗り分け、最も多い色を受け継ぐ。3つの親の色がすべて違えば4つ目の色になる
+    Blend,       // 4色のパレットで塗り分け、親の色を平均した色を受け継ぐ
+}
+
+// Gキーで切り替える順番
+pub const ALL: [ColourMode; 4] = [ColourMode::Positional, ColourMode::Immigration, ColourMode::QuadLife, ColourMode::Blend];
+
+// Immigrationで使う2色
+const IMMIGRATION_PALETTE: [[f32; 3]; 2] = [[0.9, 0.2, 0.2], [0.2, 0.4, 0.9]];
+// QuadLifeとBlendで使う4色
+const QUADLIFE_PALETTE: [[f32; 3]; 4] = [[0.9, 0.2, 0.2], [0.2, 0.4, 0.9], [0.2, 0.8, 0.3], [0.9, 0.8, 0.2]];
+
+impl ColourMode {
+    // フィールドを生成するときにセルを塗り分ける色を返すメソッド。位置で色が決まるモードではNoneを返す
+    // 多数決が意味を持つよう、色を受け継ぐモードではセルごとに違う色ではなく、少ない色から選ぶ
+    pub fn palette(&self) -> Option<&'static [[f32; 3]]> {
+        match self {
+            ColourMode::Positional => None,
+            ColourMode::Immigration => Some(&IMMIGRATION_PALETTE),
+            ColourMode::QuadLife | ColourMode::Blend => Some(&QUADLIFE_PALETTE),
+        }
+    }
+
+    // cellsの色を、乱数の種seedからパレットの色で塗り直すためのメソッド。パレットを使わないモードでは何もしない
+    // 生死を決める乱数とは別の系列を使うので、同じ種から生成されるセルの生死は変わらない
+    pub fn repaint<'a>(&self, cells: impl Iterator<Item = &'a mut Cell>, seed: u64) {
+        let Some(palette) = self.palette() else {
+            return;
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        rng.set_stream(1);
+        for cell in cells {
+            cell.rgb = palette[rng.gen_range(0..palette.len())];
+        }
+    }
+
+    // 親の色parentsから、新しく生まれたセルの色を決めるためのメソッド
+    // 位置で色が決まるモードや、親がいない場合はNoneを返す
+    pub fn inherit(&self, parents: &[[f32; 3]]) -> Option<[f32; 3]> {
+        if parents.is_empty() {
+            return None;
+        }
+        match self {
+            ColourMode::Positional => None,
+            ColourMode::Immigration | ColourMode::QuadLife => {
+                // QuadLifeでは、3つの親の色がすべて違うとき、どの親も持っていないパレットの色になる
+                let distinct = |i: usize| parents[..i].iter().all(|parent| *parent != parents[i]);
+                if *self == ColourMode::QuadLife && parents.len() == 3 && distinct(1) && distinct(2) {
+                    if let Some(missing) = QUADLIFE_PALETTE.iter().find(|rgb| !parents.contains(rgb)) {
+                        return Some(*missing);
+                    }
+                }
+                // 同数のときは、近傍の並びで先に現れた色を選ぶ
+                let count = |rgb: &[f32; 3]| parents.iter().filter(|&parent| parent == rgb).count();
+                let mut majority = parents[0];
+                for parent in &parents[1..] {
+                    if count(&majority) < count(parent) {
+                        majority = *parent;
+                    }
+                }
+                Some(majority)
+            },
+            ColourMode::Blend => {
+                let mut sum = [0.0; 3];
+                for parent in parents {
+                    for (total, channel) in sum.iter_mut().zip(parent) {
+                        *total += channel;
+                    }
+                }
+                Some(sum.map(|total| total / parents.len() as f32))
+            },
+        }
+    }
+
+    // ALLの中で次のモードを返すメソッド
+    pub fn next(&self) -> ColourMode {
+        let i = ALL.iter().position(|mode| mode == self).unwrap();
+        ALL[(i + 1) % ALL.len()]
+    }
+}
+
+impl fmt::Display for ColourMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ColourMode::Positional => "positional",
+            ColourMode::Immigration => "immigration",
+            ColourMode::QuadLife => "quadlife",
+            ColourMode::Blend => "blend",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ColourMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColourMode, String> {
+        ALL.iter()
+            .find(|mode| mode.to_string().eq_ignore_ascii_case(s.trim()))
+            .copied()
+            .ok_or_else(|| format!("unknown colour mode {:?}: expected one of positional, immigration, quadlife, blend", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [f32; 3] = [1.0, 0.0, 0.0];
+    const BLUE: [f32; 3] = [0.0, 0.0, 1.0];
+
+    #[test]
+    fn majority_takes_the_most_common_colour() {
+        for mode in [ColourMode::Immigration, ColourMode::QuadLife] {
+            assert_eq!(mode.inherit(&[BLUE, RED, RED]), Some(RED));
+            assert_eq!(mode.inherit(&[BLUE, RED]), Some(BLUE));
+            assert_eq!(mode.inherit(&[]), None);
+        }
+    }
+
+    #[test]
+    fn quadlife_takes_the_missing_colour_from_three_different_parents() {
+        let [red, blue, green, yellow] = QUADLIFE_PALETTE;
+        assert_eq!(ColourMode::QuadLife.inherit(&[red, blue, green]), Some(yellow));
+        assert_eq!(ColourMode::QuadLife.inherit(&[yellow, blue, red]), Some(green));
+        assert_eq!(ColourMode::QuadLife.inherit(&[red, blue, red]), Some(red));
+        assert_eq!(ColourMode::Immigration.inherit(&[red, blue, green]), Some(red));
+    }
+
+    #[test]
+    fn blend_averages_the_parents() {
+        assert_eq!(ColourMode::Blend.inherit(&[RED, BLUE]), Some([0.5, 0.0, 0.5]));
+        assert_eq!(ColourMode::Positional.inherit(&[RED, BLUE]), None);
+    }
+
+    #[test]
+    fn parses_and_cycles_names() {
+        for mode in ALL {
+            assert_eq!(mode.to_string().parse::<ColourMode>().unwrap(), mode);
+        }
+        assert!("rainbow".parse::<ColourMode>().is_err());
+        assert_eq!(ColourMode::Blend.next(), ColourMode::Positional);
+    }
+}
@@ -0,0 +1,760 @@
+use std::thread;
+
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+use crate::boundary::Boundary;
+use crate::colour::ColourMode;
+use crate::pattern::Pattern;
+use crate::rule::Rule;
+
+// フィールドの状態を管理するための構造体
+#[derive(Clone)]
+pub struct Field {
+    pub col_n: u32,              // フィールドの列数（横方向のセル数）
+    pub row_n: u32,              // フィールドの行数（縦方向のセル数）
+    pub matrix: Vec<Vec<Cell>>,  // セルを行列の中に格納する
+    pub rule: Rule,              // 誕生・生存の条件となるルール
+    pub boundary: Boundary,      // フィールドの端の扱い
+    pub threads: usize,          // 次の状態を計算するスレッドの数（1なら呼び出し元のスレッドだけで計算する）
+    pub colour_mode: ColourMode, // 新しく生まれたセルの色の決め方
+}
+
+impl Field {
+    // フィールドを生成するためのメソッド。乱数の種は毎回ランダムに選ぶ
+    pub fn new(row_n: u32, col_n: u32, alive_ratio: f32) -> Field {
+        Field::with_seed(row_n, col_n, alive_ratio, thread_rng().gen())
+    }
+
+    // 乱数の種seedからフィールドを生成するためのメソッド。同じ種からはいつも同じセルの状態と色が生成される
+    pub fn with_seed(row_n: u32, col_n: u32, alive_ratio: f32, seed: u64) -> Field {
+        let matrix = vec![vec![Cell::new(CellState::Dead, 0.0, 0.0, 0.0); row_n as usize]; col_n as usize];
+        let mut field = Field{
+            row_n,
+            col_n,
+            matrix,
+            rule: Rule::default(),
+            boundary: Boundary::default(),
+            threads: 1,
+            colour_mode: ColourMode::default(),
+        };
+        field.reseed(alive_ratio, seed);
+        field
+    }
+
+    // 現在のフィールドの状態をもとに次の状態へ移行するためのメソッド
+    pub fn iterate(&mut self) {
+        // 近傍のセルの相対位置は、行の偶奇ごとに一度だけ求めておく（六角形グリッド以外では同じになる）
+        let offsets = [self.rule.neighbourhood.offsets(0), self.rule.neighbourhood.offsets(1)];
+        // 半径の大きい近傍では、生存セルの数を累積和でまとめて数えておく
+        let neighbor_counts = if self.rule.neighbourhood.radius() > 1 {
+            Some(self.rule.neighbourhood.count_all(self.col_n, self.row_n, |x, y| self.alive_at(x, y) as u32))
+        } else {
+            None
+        };
+        // x列目の次の状態を計算
+        let next_col = |x: usize| {
+            self.matrix[x].iter().enumerate().map(|(y, cell)| {
+                let neighbor_count = match &neighbor_counts {
+                    Some(counts) => counts[x][y],
+                    None => self.count_alive(x, y, &offsets[y % 2]),
+                };
+                // 誕生・生存・死滅はルールに従って決める
+                match self.rule.next_state(cell.state, neighbor_count) {
+                    // 新しく生まれたセルは、色を受け継ぐモードであれば親の色になる
+                    CellState::Alive if cell.state != CellState::Alive => {
+                        match self.colour_mode.inherit(&self.colours_at(x, y, &offsets[y % 2])) {
+                            Some(rgb) => cell.born_clone(rgb),
+                            None => cell.alive_clone(),
+                        }
+                    },
+                    CellState::Alive => cell.alive_clone(),
+                    CellState::Dying(age) => cell.dying_clone(age),
+                    CellState::Dead => cell.dead_clone(),
+                    state => cell.next_clone(state),
+                }
+            }).collect::<Vec<_>>()
+        };
+        let col_n = self.col_n as usize;
+        let new_matrix = if self.threads <= 1 {
+            (0..col_n).map(next_col).collect::<Vec<_>>()
+        } else {
+            // 列をスレッドの数だけの帯に分けて並列に計算し、左の帯から順につなぎ合わせる
+            // 各セルの計算は現在の状態しか読まないため、結果は1スレッドで計算したときと同じになる
+            let band_width = col_n.div_ceil(self.threads).max(1);
+            let next_col = &next_col;
+            thread::scope(|scope| {
+                let bands = (0..col_n).step_by(band_width).map(|x0| {
+                    scope.spawn(move || (x0..(x0 + band_width).min(col_n)).map(next_col).collect::<Vec<_>>())
+                }).collect::<Vec<_>>();
+                bands.into_iter().flat_map(|band| band.join().unwrap()).collect::<Vec<_>>()
+            })
+        };
+        self.matrix = new_matrix;
+    }
+
+    // 乱数の種seedから、すべてのセルの状態と色を生成し直すためのメソッド
+    // どの環境でも同じ乱数列になるよう、アルゴリズムが固定されているChaCha8を使う
+    pub fn reseed(&mut self, alive_ratio: f32, seed: u64) {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        for cell in self.matrix.iter_mut().flatten() {
+            // alive_ratioの確率で生きているセルを生成する
+            let state = if rng.gen::<f32>() < alive_ratio { CellState::Alive } else { CellState::Dead };
+            // セルの色は生成時に決定。同じ色が次世代でも受け継がれる
+            *cell = Cell::new(state, 0.0, rng.gen_range(0.0..=0.4), rng.gen_range(0.6..=1.0));
+        }
+        self.recolour(seed);
+    }
+
+    // 色を受け継ぐモードのとき、すべてのセルを乱数の種seedからパレットの色で塗り直すためのメソッド
+    // 色の決め方を切り替えたときや、生成した後で色の決め方を設定したときに呼ぶ
+    pub fn recolour(&mut self, seed: u64) {
+        self.colour_mode.repaint(self.matrix.iter_mut().flatten(), seed);
+    }
+
+    // フィールドの大きさをrow_n行col_n列に変えるためのメソッド
+    // 残るセルは同じ座標のまま（左上を基準に）残し、広がった部分には座標から決まる色の死んだセルを置く
+    pub fn resize(&mut self, row_n: u32, col_n: u32) {
+        self.matrix.truncate(col_n as usize);
+        for (x, col) in self.matrix.iter_mut().enumerate() {
+            col.truncate(row_n as usize);
+            while col.len() < row_n as usize {
+                col.push(Cell::positional((x as i64, col.len() as i64)));
+            }
+        }
+        while self.matrix.len() < col_n as usize {
+            let x = self.matrix.len() as i64;
+            self.matrix.push((0..row_n).map(|y| Cell::positional((x, y as i64))).collect());
+        }
+        self.col_n = col_n;
+        self.row_n = row_n;
+    }
+
+    // すべてのセルを死んだ状態にするためのメソッド
+    pub fn clear(&mut self) {
+        // 消したセルは、一度も生きたことのないセルとして扱う
+        for cell in self.matrix.iter_mut().flatten() {
+            *cell = Cell { state: CellState::Dead, age: 0, dead_for: NEVER_ALIVE, ..*cell };
+        }
+    }
+
+    // パターンの左上が(offset_x, offset_y)に来るように配置するためのメソッド
+    // パターンの範囲内のセルは上書きされ、フィールドからはみ出した部分は無視する
+    // セルの状態番号は、パターンにルールがあればそのルールで（なければフィールドのルールで）状態に変える
+    pub fn place_pattern(&mut self, pattern: &Pattern, offset_x: i64, offset_y: i64) {
+        let rule = pattern.rule.unwrap_or(self.rule);
+        // パターンの範囲のうちフィールドに重なる部分。ヘッダーの大きさがどれだけ大きくても、フィールドより大きくはならない
+        let (x0, x1) = (offset_x.max(0), offset_x.saturating_add_unsigned(pattern.width as u64).min(self.col_n as i64));
+        let (y0, y1) = (offset_y.max(0), offset_y.saturating_add_unsigned(pattern.height as u64).min(self.row_n as i64));
+        if x1 <= x0 || y1 <= y0 {
+            return;
+        }
+        let height = (y1 - y0) as usize;
+        let mut states = vec![vec![CellState::Dead; height]; (x1 - x0) as usize];
+        for (&(px, py), &index) in pattern.cells.iter().zip(&pattern.states) {
+            let x = offset_x.saturating_add_unsigned(px as u64);
+            let y = offset_y.saturating_add_unsigned(py as u64);
+            if (x0..x1).contains(&x) && (y0..y1).contains(&y) {
+                states[(x - x0) as usize][(y - y0) as usize] = rule.state(index);
+            }
+        }
+        for (x, col) in (x0 as usize..).zip(&states) {
+            for (y, &state) in (y0 as usize..).zip(col) {
+                let cell = &mut self.matrix[x][y];
+                *cell = cell.next_clone(state);
+            }
+        }
+    }
+
+    // パターンをフィールドの中央に配置するためのメソッド
+    pub fn place_pattern_centered(&mut self, pattern: &Pattern) {
+        let offset_x = (self.col_n as i64 - i64::try_from(pattern.width).unwrap_or(i64::MAX)) / 2;
+        let offset_y = (self.row_n as i64 - i64::try_from(pattern.height).unwrap_or(i64::MAX)) / 2;
+        self.place_pattern(pattern, offset_x, offset_y);
+    }
+
+    // フィールド全体を現在のルール付きのパターンに変換するためのメソッド
+    // 死んだセル以外のセルを、ルールでの状態番号とともに書き出す
+    pub fn to_pattern(&self) -> Pattern {
+        let (mut cells, mut states) = (vec![], vec![]);
+        for y in 0..self.row_n as usize {
+            for x in 0..self.col_n as usize {
+                let state = self.get_cell(x, y).state;
+                if state != CellState::Dead {
+                    cells.push((x, y));
+                    states.push(self.rule.index(state));
+                }
+            }
+        }
+        Pattern {
+            width: self.col_n as usize,
+            height: self.row_n as usize,
+            cells,
+            states,
+            rule: Some(self.rule),
+        }
+    }
+
+    // フィールドの中から特定の場所のセルを取り出すためのメソッド
+    pub fn get_cell(&self, x: usize, y: usize) -> Cell {
+        self.matrix[x][y]
+    }
+
+    // 生きているセル（Wireworldでは電子の頭）の数を返すメソッド
+    pub fn population(&self) -> u64 {
+        self.matrix.iter().flatten().filter(|cell| self.rule.counts(cell.state)).count() as u64
+    }
+
+    // 与えられた場所に接しているセルのうち生存しているものの数を返すメソッド
+    pub fn alive_neighbour_count_from(&self, from_x: usize, from_y: usize) -> u32 {
+        self.count_alive(from_x, from_y, &self.rule.neighbourhood.offsets(from_y))
+    }
+
+    // 与えられた場所に接している生存セル（新しく生まれるセルの親）の色を、近傍の並び順で返すメソッド
+    pub fn parent_colours(&self, from_x: usize, from_y: usize) -> Vec<[f32; 3]> {
+        self.colours_at(from_x, from_y, &self.rule.neighbourhood.offsets(from_y))
+    }
+
+    // 与えられた場所から相対位置offsetsにあるセルのうち、生存しているものの数を返すメソッド
+    fn count_alive(&self, from_x: usize, from_y: usize, offsets: &[(i32, i32)]) -> u32 {
+        let mut alive_count = 0u32;
+
+        // ルールの近傍に含まれるセルを順に調べる
+        for &(offset_x, offset_y) in offsets {
+            let x = from_x as i32 + offset_x;
+            let y = from_y as i32 + offset_y;
+            if self.alive_at(x, y) {
+                alive_count += 1;
+            }
+        }
+
+        alive_count
+    }
+
+    // 与えられた場所から相対位置offsetsにある生存セルの色を、offsetsの並び順で返すメソッド
+    fn colours_at(&self, from_x: usize, from_y: usize, offsets: &[(i32, i32)]) -> Vec<[f32; 3]> {
+        if self.colour_mode == ColourMode::Positional {
+            return vec![];
+        }
+        offsets.iter()
+            .filter_map(|&(offset_x, offset_y)| {
+                self.boundary.resolve(from_x as i32 + offset_x, from_y as i32 + offset_y, self.col_n, self.row_n)
+            })
+            .map(|(x, y)| self.get_cell(x, y))
+            .filter(|cell| cell.state == CellState::Alive)
+            .map(|cell| cell.rgb)
+            .collect()
+    }
+
+    // 領域外を含む座標(x, y)のセルが生存しているか（Wireworldでは電子の頭か）を返すメソッド
+    // 境界の種類に応じて座標を変換し、対応するセルが無ければ死んでいるものとみなす
+    pub fn alive_at(&self, x: i32, y: i32) -> bool {
+        match self.boundary.resolve(x, y, self.col_n, self.row_n) {
+            Some((x, y)) => self.rule.counts(self.get_cell(x, y).state),
+            None => false,
+        }
+    }
+}
+
+// 一度も生きたことのないセルのdead_for
+pub const NEVER_ALIVE: u32 = u32::MAX;
+
+// 個別のセルを表現するための構造体。CloneとCopyを利用できるようにする
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Cell {
+    pub state: CellState,
+    pub rgb: [f32; 3],
+    pub age: u32,      // 生まれてから生き続けている世代数（生まれた世代は0）。死んだ後は死ぬ前の値のまま
+    pub dead_for: u32, // 生存をやめてからの世代数（生きている間は0、一度も生きたことがなければNEVER_ALIVE）
+}
+
+impl Cell {
+    pub fn new(state: CellState, r: f32, g: f32, b: f32) -> Cell {
+        let dead_for = if state == CellState::Alive { 0 } else { NEVER_ALIVE };
+        Cell { state, rgb: [r, g, b], age: 0, dead_for }
+    }
+
+    // 座標(x, y)から決まる色の、一度も生きたことのないセルを作るメソッド
+    // 同じ場所のセルはいつも同じ色になるため、端のないフィールドや広げたフィールドでも乱数を使わずに色を決められる
+    pub fn positional((x, y): (i64, i64)) -> Cell {
+        // 座標を混ぜ合わせた64ビットの値から、フィールドを生成するときと同じ範囲の緑と青を取り出す
+        let mut hash = (x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ (y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+        hash ^= hash >> 31;
+        hash = hash.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        hash ^= hash >> 29;
+        let unit = |bits: u64| (bits & 0xffff) as f32 / 0xffff as f32;
+        Cell::new(CellState::Dead, 0.0, unit(hash) * 0.4, 0.6 + unit(hash >> 16) * 0.4)
+    }
+
+    // 同じセルを次の世代でstateにしたクローンを返すメソッド。年齢と、死んでからの世代数も1世代ぶん進める
+    pub fn next_clone(&self, state: CellState) -> Cell {
+        let mut clone = *self;
+        clone.state = state;
+        if state == CellState::Alive {
+            clone.age = if self.state == CellState::Alive { self.age.saturating_add(1) } else { 0 };
+            clone.dead_for = 0;
+        } else {
+            clone.dead_for = self.dead_for.saturating_add(1);
+        }
+        clone
+    }
+
+    // 同じセルの生きているクローンを返すメソッド
+    pub fn alive_clone(&self) -> Cell {
+        self.next_clone(CellState::Alive)
+    }
+
+    // 同じセルを、色をrgbにして生まれさせたクローンを返すメソッド
+    pub fn born_clone(&self, rgb: [f32; 3]) -> Cell {
+        Cell { rgb, ..self.next_clone(CellState::Alive) }
+    }
+
+    // 同じセルの死につつあるクローンを返すメソッド
+    pub fn dying_clone(&self, age: u8) -> Cell {
+        self.next_clone(CellState::Dying(age))
+    }
+
+    // 同じセルの死んでいるクローンを返すメソッド
+    pub fn dead_clone(&self) -> Cell {
+        self.next_clone(CellState::Dead)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CellState {
+    Alive,     // 生存
+    Dying(u8), // 死につつある（Generationsルールのみ）。値は生存をやめてからの世代数
+    Dead,      // 死滅（Wireworldでは空）
+    Head,      // 電子の頭（Wireworldのみ）
+    Tail,      // 電子の尾（Wireworldのみ）
+    Conductor, // 導体（Wireworldのみ）
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary;
+
+    // 指定した座標のセルだけが生きているフィールドを生成する
+    fn field_with(col_n: u32, row_n: u32, rule: Rule, alive: &[(usize, usize)]) -> Field {
+        let mut field = Field::new(row_n, col_n, 0.0);
+        field.rule = rule;
+        for &(x, y) in alive {
+            field.matrix[x][y].state = CellState::Alive;
+        }
+        field
+    }
+
+    // 生きているセルの座標を昇順に並べて返す
+    fn alive_cells(field: &Field) -> Vec<(usize, usize)> {
+        let mut cells = vec![];
+        for x in 0..field.col_n as usize {
+            for y in 0..field.row_n as usize {
+                if let CellState::Alive = field.get_cell(x, y).state {
+                    cells.push((x, y));
+                }
+            }
+        }
+        cells
+    }
+
+    // 乱数の種から決まる、およそ半分のセルが生きているフィールドを生成する
+    fn seeded_field(col_n: u32, row_n: u32, seed: u64) -> Field {
+        let mut field = Field::new(row_n, col_n, 0.0);
+        let mut rng = StdRng::seed_from_u64(seed);
+        for cell in field.matrix.iter_mut().flatten() {
+            if rng.gen_bool(0.5) {
+                cell.state = CellState::Alive;
+            }
+        }
+        field
+    }
+
+    const BLINKER: [(usize, usize); 3] = [(1, 2), (2, 2), (3, 2)];
+    const BLOCK: [(usize, usize); 4] = [(1, 1), (1, 2), (2, 1), (2, 2)];
+
+    #[test]
+    fn conway_blinker_oscillates_and_block_is_still() {
+        let mut field = field_with(5, 5, Rule::conway(), &BLINKER);
+        field.iterate();
+        assert_eq!(alive_cells(&field), vec![(2, 1), (2, 2), (2, 3)]);
+        field.iterate();
+        assert_eq!(alive_cells(&field), BLINKER.to_vec());
+
+        let mut field = field_with(4, 4, Rule::conway(), &BLOCK);
+        field.iterate();
+        assert_eq!(alive_cells(&field), BLOCK.to_vec());
+    }
+
+    #[test]
+    fn highlife_births_on_six_neighbours() {
+        // 中央(2, 2)の死んだセルがちょうど6個の生存セルに囲まれている
+        let ring = [(1, 1), (2, 1), (3, 1), (1, 3), (2, 3), (3, 3)];
+        let mut conway = field_with(5, 5, Rule::conway(), &ring);
+        let mut highlife = field_with(5, 5, Rule::preset(1).unwrap(), &ring);
+        conway.iterate();
+        highlife.iterate();
+        assert!(!alive_cells(&conway).contains(&(2, 2)));
+        assert!(alive_cells(&highlife).contains(&(2, 2)));
+
+        // B3/S23と共通する部分はそのまま
+        let mut field = field_with(5, 5, Rule::preset(1).unwrap(), &BLINKER);
+        field.iterate();
+        field.iterate();
+        assert_eq!(alive_cells(&field), BLINKER.to_vec());
+    }
+
+    #[test]
+    fn seeds_kills_every_live_cell() {
+        // 横に並んだ2セルは消え、その上下に2セルずつ誕生する
+        let mut field = field_with(6, 6, Rule::preset(2).unwrap(), &[(2, 2), (3, 2)]);
+        field.iterate();
+        assert_eq!(alive_cells(&field), vec![(2, 1), (2, 3), (3, 1), (3, 3)]);
+
+        let mut field = field_with(5, 5, Rule::preset(2).unwrap(), &[(2, 2)]);
+        field.iterate();
+        assert!(alive_cells(&field).is_empty());
+    }
+
+    #[test]
+    fn day_and_night_keeps_block_and_dissolves_blinker() {
+        let mut field = field_with(4, 4, Rule::preset(3).unwrap(), &BLOCK);
+        field.iterate();
+        assert_eq!(alive_cells(&field), BLOCK.to_vec());
+
+        // 中央は接触数2で死滅し、中央の上下が接触数3で誕生する
+        let mut field = field_with(5, 5, Rule::preset(3).unwrap(), &BLINKER);
+        field.iterate();
+        assert_eq!(alive_cells(&field), vec![(2, 1), (2, 3)]);
+        field.iterate();
+        assert!(alive_cells(&field).is_empty());
+    }
+
+    #[test]
+    fn patterns_round_trip_through_the_field() {
+        let glider = Pattern::from_rle("x = 3, y = 3, rule = B36/S23\nbo$2bo$3o!").unwrap();
+        let mut field = Field::new(7, 9, 0.0);
+        field.place_pattern_centered(&glider);
+        assert_eq!(alive_cells(&field), vec![(3, 4), (4, 2), (4, 4), (5, 3), (5, 4)]);
+
+        // はみ出した部分は無視される
+        field.place_pattern(&glider, 7, -1);
+        assert!(alive_cells(&field).contains(&(8, 1)));
+        assert!(alive_cells(&field).contains(&(7, 1)));
+
+        field.clear();
+        field.place_pattern(&glider, 1, 2);
+        let exported = Pattern::from_rle(&field.to_pattern().to_rle()).unwrap();
+        assert_eq!(exported.rule, Some(Rule::conway()));
+        let mut reloaded = Field::new(7, 9, 0.0);
+        reloaded.place_pattern(&exported, 0, 0);
+        assert_eq!(alive_cells(&reloaded), alive_cells(&field));
+    }
+
+    const GLIDER: [(usize, usize); 5] = [(0, 2), (1, 0), (1, 2), (2, 1), (2, 2)];
+
+    #[test]
+    fn glider_survives_forever_on_a_torus() {
+        // 10x6のトーラスでは、4世代で(1, 1)進むグライダーはlcm(10, 6) * 4 = 120世代で元の位置に戻る
+        let mut field = field_with(10, 6, Rule::conway(), &GLIDER);
+        field.boundary = Boundary::Torus;
+        for generation in 1..=1200 {
+            field.iterate();
+            assert_eq!(alive_cells(&field).len(), 5, "generation {}", generation);
+            if generation % 120 == 0 {
+                assert_eq!(alive_cells(&field), GLIDER.to_vec(), "generation {}", generation);
+            } else if generation % 4 == 0 {
+                assert_ne!(alive_cells(&field), GLIDER.to_vec(), "generation {}", generation);
+            }
+        }
+    }
+
+    #[test]
+    fn glider_crashes_into_a_dead_boundary() {
+        let mut field = field_with(10, 6, Rule::conway(), &GLIDER);
+        for _ in 0..120 {
+            field.iterate();
+        }
+        // 下端にぶつかってブロックになって止まる
+        assert_eq!(alive_cells(&field), vec![(4, 4), (4, 5), (5, 4), (5, 5)]);
+    }
+
+    #[test]
+    fn glider_survives_on_a_klein_bottle() {
+        let mut field = field_with(8, 8, Rule::conway(), &GLIDER);
+        field.boundary = Boundary::KleinBottle;
+        for _ in 0..400 {
+            field.iterate();
+            assert_eq!(alive_cells(&field).len(), 5);
+        }
+    }
+
+    #[test]
+    fn brians_brain_cells_pass_through_a_dying_state() {
+        let mut field = field_with(6, 6, Rule::preset(4).unwrap(), &[(2, 2), (3, 2)]);
+        field.iterate();
+        assert_eq!(field.get_cell(2, 2).state, CellState::Dying(1));
+        assert_eq!(field.get_cell(3, 2).state, CellState::Dying(1));
+        assert_eq!(alive_cells(&field), vec![(2, 1), (2, 3), (3, 1), (3, 3)]);
+
+        // 死につつあるセルは数えられず、そこに誕生することもない
+        field.iterate();
+        assert_eq!(field.get_cell(2, 2).state, CellState::Dead);
+        assert_eq!(field.get_cell(2, 1).state, CellState::Dying(1));
+        assert_eq!(alive_cells(&field), vec![(1, 2), (2, 0), (2, 4), (3, 0), (3, 4), (4, 2)]);
+    }
+
+    #[test]
+    fn star_wars_cells_fade_over_two_generations() {
+        let mut field = field_with(5, 5, Rule::preset(5).unwrap(), &[(2, 2)]);
+        field.iterate();
+        assert_eq!(field.get_cell(2, 2).state, CellState::Dying(1));
+        field.iterate();
+        assert_eq!(field.get_cell(2, 2).state, CellState::Dying(2));
+        field.iterate();
+        assert_eq!(field.get_cell(2, 2).state, CellState::Dead);
+    }
+
+    #[test]
+    fn neighbour_counts_follow_the_rule_neighbourhood() {
+        let ring = [(1, 1), (2, 1), (3, 1), (1, 2), (3, 2), (1, 3), (2, 3), (3, 3)];
+        let mut field = field_with(5, 5, Rule::conway(), &ring);
+        assert_eq!(field.alive_neighbour_count_from(2, 2), 8);
+        field.rule = "B1/S1V".parse().unwrap();
+        assert_eq!(field.alive_neighbour_count_from(2, 2), 4);
+        field.rule = "B2/S34H".parse().unwrap();
+        // (2, 2)は偶数行なので左上(1, 1)と左下(1, 3)が接する
+        assert_eq!(field.alive_neighbour_count_from(2, 2), 6);
+        field.rule = Rule::preset(6).unwrap();
+        assert_eq!(field.alive_neighbour_count_from(2, 2), 8);
+        assert_eq!(field.alive_neighbour_count_from(4, 4), 8);
+    }
+
+    #[test]
+    fn larger_than_life_matches_direct_counting() {
+        // 累積和で数えた結果が1セルずつ数えた結果と一致することを、境界の種類ごとに確かめる
+        for boundary in boundary::ALL {
+            let mut field = Field::new(20, 30, 0.45);
+            field.rule = "R3,C0,M1,S8..18,B9..13,NM".parse().unwrap();
+            field.boundary = boundary;
+            for _ in 0..5 {
+                let expected: Vec<Vec<CellState>> = (0..30).map(|x| {
+                    (0..20).map(|y| {
+                        let count = field.alive_neighbour_count_from(x, y);
+                        field.rule.next_state(field.get_cell(x, y).state, count)
+                    }).collect()
+                }).collect();
+                field.iterate();
+                let actual: Vec<Vec<CellState>> = field.matrix.iter()
+                    .map(|col| col.iter().map(|cell| cell.state).collect())
+                    .collect();
+                assert_eq!(actual, expected, "{}", boundary);
+            }
+        }
+    }
+
+    #[test]
+    fn rule_can_be_switched_between_generations() {
+        let mut field = field_with(6, 6, Rule::conway(), &[(2, 2), (3, 2)]);
+        field.iterate();
+        assert!(alive_cells(&field).is_empty());
+
+        let mut field = field_with(6, 6, Rule::conway(), &[(2, 2), (3, 2)]);
+        field.rule = Rule::preset(2).unwrap();
+        field.iterate();
+        assert_eq!(alive_cells(&field).len(), 4);
+    }
+
+    #[test]
+    fn parallel_iteration_matches_serial() {
+        // 列数で割り切れないスレッド数や、列数より多いスレッド数でも同じ結果になることを確かめる
+        let mut serial = seeded_field(61, 40, 9);
+        serial.boundary = Boundary::Torus;
+        let mut parallels: Vec<Field> = [2, 3, 8, 100].iter().map(|&threads| {
+            let mut field = seeded_field(61, 40, 9);
+            field.boundary = Boundary::Torus;
+            field.threads = threads;
+            field
+        }).collect();
+        for generation in 1..=1000 {
+            serial.iterate();
+            for parallel in parallels.iter_mut() {
+                parallel.iterate();
+                assert_eq!(alive_cells(parallel), alive_cells(&serial),
+                    "{} threads at generation {}", parallel.threads, generation);
+            }
+        }
+        assert!(serial.population() > 0);
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_soup() {
+        let cells = |field: &Field| -> Vec<(CellState, [f32; 3])> {
+            field.matrix.iter().flatten().map(|cell| (cell.state, cell.rgb)).collect()
+        };
+        let field = Field::with_seed(20, 30, 0.3, 42);
+        assert_eq!(cells(&field), cells(&Field::with_seed(20, 30, 0.3, 42)));
+        assert_ne!(cells(&field), cells(&Field::with_seed(20, 30, 0.3, 43)));
+
+        let mut reseeded = Field::with_seed(20, 30, 0.3, 7);
+        reseeded.reseed(0.3, 42);
+        assert_eq!(cells(&reseeded), cells(&field));
+    }
+
+    #[test]
+    fn newborn_cells_inherit_their_parents_colours() {
+        // 赤いブリンカーの横棒が縦棒になるとき、上下に生まれる2つのセルの色を確かめる
+        const RED: [f32; 3] = [1.0, 0.0, 0.0];
+        const BLUE: [f32; 3] = [0.0, 0.0, 1.0];
+        for (colour_mode, expected) in [
+            (ColourMode::Positional, None),
+            (ColourMode::Immigration, Some(RED)),
+            (ColourMode::QuadLife, Some(RED)),
+            (ColourMode::Blend, Some([2.0 / 3.0, 0.0, 1.0 / 3.0])),
+        ] {
+            let mut field = field_with(5, 5, Rule::conway(), &[(1, 2), (2, 2), (3, 2)]);
+            field.colour_mode = colour_mode;
+            field.matrix[1][2].rgb = RED;
+            field.matrix[2][2].rgb = RED;
+            field.matrix[3][2].rgb = BLUE;
+            let positional = [field.matrix[2][1].rgb, field.matrix[2][3].rgb];
+            field.iterate();
+            assert_eq!(alive_cells(&field).len(), 3);
+            for (i, y) in [1, 3].into_iter().enumerate() {
+                assert_eq!(field.matrix[2][y].rgb, expected.unwrap_or(positional[i]), "{}", colour_mode);
+            }
+            // 生き残ったセルの色は変わらない
+            assert_eq!(field.matrix[2][2].rgb, RED);
+        }
+    }
+
+    #[test]
+    fn seeded_colonies_keep_their_own_colours() {
+        // 左右に離して置いた2つのR-pentominoを、パレットの別々の色で塗る
+        let r_pentomino = [(1, 0), (2, 0), (0, 1), (1, 1), (1, 2)];
+        let mut field = Field::with_seed(40, 80, 0.0, 1);
+        field.colour_mode = ColourMode::Immigration;
+        field.recolour(1);
+        let palette = ColourMode::Immigration.palette().unwrap();
+        for (x0, colour) in [(15, palette[0]), (60, palette[1])] {
+            for (x, y) in r_pentomino {
+                let cell = &mut field.matrix[x0 + x][19 + y];
+                *cell = cell.alive_clone();
+                cell.rgb = colour;
+            }
+        }
+        for _ in 0..25 {
+            field.iterate();
+        }
+        // 生まれたセルはどれも、同じコロニーの親から色を受け継いでいる
+        let cells = alive_cells(&field);
+        for &(x, y) in &cells {
+            let expected = if x < 40 { palette[0] } else { palette[1] };
+            assert_eq!(field.matrix[x][y].rgb, expected, "cell ({}, {})", x, y);
+        }
+        assert!(cells.iter().any(|&(x, _)| x < 40) && cells.iter().any(|&(x, _)| 40 <= x));
+    }
+
+    #[test]
+    fn palette_modes_seed_only_palette_colours() {
+        let mut field = Field::with_seed(20, 30, 0.5, 3);
+        field.colour_mode = ColourMode::QuadLife;
+        field.reseed(0.5, 3);
+        let positional = Field::with_seed(20, 30, 0.5, 3);
+        let palette = ColourMode::QuadLife.palette().unwrap();
+        for (cell, other) in field.matrix.iter().flatten().zip(positional.matrix.iter().flatten()) {
+            assert!(palette.contains(&cell.rgb));
+            // 色の決め方によらず、同じ種からは同じ生死のセルが生成される
+            assert_eq!(cell.state, other.state);
+        }
+        for colour in palette {
+            assert!(field.matrix.iter().flatten().any(|cell| cell.rgb == *colour));
+        }
+    }
+
+    #[test]
+    fn tracks_age_and_time_since_death() {
+        // ブロックは生き続け、ブリンカーの両端は1世代ごとに死んでは生まれる
+        let mut field = field_with(10, 6, Rule::conway(), &[(1, 1), (1, 2), (2, 1), (2, 2), (6, 2), (6, 3), (6, 4)]);
+        for _ in 0..5 {
+            field.iterate();
+        }
+        assert_eq!(field.matrix[1][1].age, 5);
+        assert_eq!((field.matrix[6][3].age, field.matrix[6][3].dead_for), (5, 0));
+        assert_eq!((field.matrix[5][3].age, field.matrix[5][3].dead_for), (0, 0));
+        assert_eq!((field.matrix[6][2].state, field.matrix[6][2].dead_for), (CellState::Dead, 1));
+        assert_eq!(field.matrix[0][0].dead_for, NEVER_ALIVE);
+
+        field.clear();
+        assert_eq!(field.matrix[6][3].dead_for, NEVER_ALIVE);
+    }
+
+    // Wireworldの多状態のRLEを、まわりを1セル空けたフィールドに置く
+    fn wireworld_field(rle: &str) -> Field {
+        let pattern = Pattern::from_rle(rle).unwrap();
+        let mut field = Field::new(pattern.height as u32 + 2, pattern.width as u32 + 2, 0.0);
+        field.rule = pattern.rule.unwrap();
+        field.place_pattern(&pattern, 1, 1);
+        field
+    }
+
+    // 右端の列のセルが電子の頭になった世代を返す
+    fn heads_at_right_end(field: &mut Field, generations: u32) -> Vec<u32> {
+        let x = field.col_n as usize - 2;
+        (1..=generations).filter(|_| {
+            field.iterate();
+            (0..field.row_n as usize).any(|y| field.get_cell(x, y).state == CellState::Head)
+        }).collect()
+    }
+
+    #[test]
+    fn wireworld_diode_passes_electrons_one_way() {
+        // 電線の切れ目を2x2の導体がはさむダイオード。左から来た電子だけが右へ抜ける
+        const DIODE: &str = "x = 10, y = 3, rule = WireWorld\n3.2C$4C.5C$3.2C!";
+        let mut forward = wireworld_field(DIODE);
+        let mut backward = Field { rule: Rule::wireworld(), ..forward.clone() };
+        backward.clear();
+        backward.place_pattern(&Pattern::from_rle(DIODE).unwrap().flipped(), 1, 1);
+        for field in [&mut forward, &mut backward] {
+            // 左端に右向きの電子（尾・頭）を置く
+            field.matrix[1][2].state = CellState::Tail;
+            field.matrix[2][2].state = CellState::Head;
+        }
+        assert_eq!(heads_at_right_end(&mut forward, 30), vec![8]);
+        assert_eq!(heads_at_right_end(&mut backward, 30), vec![]);
+        assert_eq!(backward.population(), 0);
+    }
+
+    #[test]
+    fn wireworld_clock_emits_an_electron_every_loop() {
+        // 10セルの輪を回り続ける電子が、1周するたびに右の電線へ電子を送り出す
+        let mut field = wireworld_field("x = 11, y = 3, rule = WireWorld\n.BA2C$C4.6C$.4C!");
+        assert_eq!(heads_at_right_end(&mut field, 60), vec![8, 18, 28, 38, 48, 58]);
+
+        // フィールドからRLEに書き出しても、状態はそのまま残る
+        let exported = Pattern::from_rle(&field.to_pattern().to_rle()).unwrap();
+        let mut reloaded = field.clone();
+        reloaded.clear();
+        reloaded.place_pattern(&exported, 0, 0);
+        let states = |field: &Field| field.matrix.iter().flatten().map(|cell| cell.state).collect::<Vec<_>>();
+        assert_eq!(states(&reloaded), states(&field));
+    }
+
+    #[test]
+    fn resizing_keeps_cells_in_place() {
+        let mut field = field_with(6, 4, Rule::conway(), &[(1, 1), (4, 3), (5, 0)]);
+        field.resize(5, 9);
+        assert_eq!((field.col_n, field.row_n), (9, 5));
+        assert_eq!((field.matrix.len(), field.matrix[8].len()), (9, 5));
+        assert_eq!(alive_cells(&field), vec![(1, 1), (4, 3), (5, 0)]);
+        // 広がった部分のセルの色は座標から決まる
+        assert_eq!(field.matrix[8][4].rgb, Cell::positional((8, 4)).rgb);
+        assert_eq!(field.matrix[2][4].rgb, Cell::positional((2, 4)).rgb);
+
+        field.resize(2, 5);
+        assert_eq!((field.matrix.len(), field.matrix[0].len()), (5, 2));
+        assert_eq!(alive_cells(&field), vec![(1, 1)]);
+        field.iterate();
+        assert!(alive_cells(&field).is_empty());
+    }
+}
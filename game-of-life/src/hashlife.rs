@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+
+use crate::neighbourhood::Neighbourhood;
+use crate::rule::Rule;
+use crate::{CellState, Field};
+
+// 四分木のノードを指すための番号。0番は死んだセル、1番は生きたセル
+type NodeId = u32;
+
+const DEAD: NodeId = 0;
+const ALIVE: NodeId = 1;
+
+// ノードの数がこれを超えたら、使われていないノードとメモを捨てる
+const MAX_NODES: usize = 1 << 22;
+
+// 一辺 2^level のマクロセル。子は左上・右上・左下・右下の順に持つ
+#[derive(Clone, Copy)]
+struct Node {
+    level: u8,
+    children: [NodeId; 4],
+    population: u64,
+}
+
+// HashLifeアルゴリズムで無限平面上のパターンを進めるための構造体
+// 同じ内容のマクロセルは一度だけ作られ、2^j 世代後の中心部分の計算結果はメモとして再利用される
+pub struct HashLife {
+    rule: Rule,
+    nodes: Vec<Node>,
+    index: HashMap<[NodeId; 4], NodeId>,   // 子の組からノードを引くための表
+    empty: Vec<NodeId>,                    // レベルごとの空のノード
+    memo: HashMap<(NodeId, u8), NodeId>,   // (ノード, j) に対する 2^j 世代後の中心部分
+    root: NodeId,
+    origin: (i64, i64),                    // rootの左上のセルの座標
+    pub generation: u64,                   // これまでに進めた世代数
+}
+
+impl HashLife {
+    // HashLifeで扱えるルールかを返すメソッド
+    // 2状態・半径1のMoore近傍で、何もないところから誕生しない（B0を含まない）ルールのみ扱える
+    pub fn supports(rule: &Rule) -> bool {
+        rule.states == 2 && rule.neighbourhood == Neighbourhood::default() && !rule.birth.contains(0)
+    }
+
+    // 空の平面を生成するためのメソッド
+    pub fn new(rule: Rule) -> HashLife {
+        assert!(HashLife::supports(&rule), "HashLife does not support {}", rule);
+        let leaf = |population| Node { level: 0, children: [DEAD; 4], population };
+        let mut hashlife = HashLife {
+            rule,
+            nodes: vec![leaf(0), leaf(1)],
+            index: HashMap::new(),
+            empty: vec![DEAD],
+            memo: HashMap::new(),
+            root: DEAD,
+            origin: (0, 0),
+            generation: 0,
+        };
+        hashlife.root = hashlife.empty_node(3);
+        hashlife
+    }
+
+    // 密なフィールドから四分木を作るためのメソッド。フィールドの左上のセルが座標(0, 0)になる
+    pub fn from_field(field: &Field) -> HashLife {
+        let mut hashlife = HashLife::new(field.rule);
+        let mut level = 3;
+        while (1u32 << level) < field.col_n.max(field.row_n) {
+            level += 1;
+        }
+        hashlife.root = hashlife.build(level, 0, 0, &|x, y| {
+            (x as u32) < field.col_n && (y as u32) < field.row_n
+                && field.get_cell(x as usize, y as usize).state == CellState::Alive
+        });
+        hashlife
+    }
+
+    // 左上が(x, y)で一辺 2^level の範囲のノードを、alive(x, y)をもとに再帰的に作る
+    fn build(&mut self, level: u8, x: i64, y: i64, alive: &dyn Fn(i64, i64) -> bool) -> NodeId {
+        if level == 0 {
+            return if alive(x, y) { ALIVE } else { DEAD };
+        }
+        let half = 1i64 << (level - 1);
+        let nw = self.build(level - 1, x, y, alive);
+        let ne = self.build(level - 1, x + half, y, alive);
+        let sw = self.build(level - 1, x, y + half, alive);
+        let se = self.build(level - 1, x + half, y + half, alive);
+        self.join([nw, ne, sw, se])
+    }
+
+    // 現在のパターンのうちフィールドの範囲(0, 0)〜(col_n, row_n)に入る部分をフィールドに書き戻すためのメソッド
+    pub fn write_to(&self, field: &mut Field) {
+        let mut cells = vec![];
+        self.collect_alive(self.root, self.origin.0, self.origin.1, (field.col_n as i64, field.row_n as i64), &mut cells);
+        let mut alive = vec![vec![false; field.row_n as usize]; field.col_n as usize];
+        for (x, y) in cells {
+            alive[x as usize][y as usize] = true;
+        }
+        // 何世代進めたかにかかわらず、年齢と死んでからの世代数は1世代ぶんだけ進める
+        for (col, alive_col) in field.matrix.iter_mut().zip(alive) {
+            for (cell, is_alive) in col.iter_mut().zip(alive_col) {
+                *cell = cell.next_clone(if is_alive { CellState::Alive } else { CellState::Dead });
+            }
+        }
+    }
+
+    // ノードに含まれる生きたセルのうち、(0, 0)〜(w, h)の範囲にあるものの座標を集める
+    fn collect_alive(&self, id: NodeId, x: i64, y: i64, (w, h): (i64, i64), cells: &mut Vec<(i64, i64)>) {
+        let node = self.nodes[id as usize];
+        let size = 1i64 << node.level;
+        if node.population == 0 || w <= x || h <= y || x + size <= 0 || y + size <= 0 {
+            return;
+        }
+        if node.level == 0 {
+            cells.push((x, y));
+            return;
+        }
+        let half = size / 2;
+        let [nw, ne, sw, se] = node.children;
+        self.collect_alive(nw, x, y, (w, h), cells);
+        self.collect_alive(ne, x + half, y, (w, h), cells);
+        self.collect_alive(sw, x, y + half, (w, h), cells);
+        self.collect_alive(se, x + half, y + half, (w, h), cells);
+    }
+
+    // 生きているセルの数
+    pub fn population(&self) -> u64 {
+        self.nodes[self.root as usize].population
+    }
+
+    // 2^j 世代まとめて進めるためのメソッド
+    pub fn step(&mut self, j: u8) {
+        // パターンが中心の1/4に収まり、2^j 世代分の広がりを受け止められる大きさになるまで外側を広げる
+        while self.level() < j + 3 || !self.is_padded(self.root) {
+            self.expand();
+        }
+        // 一回り大きくしてから中心を取り出すと、元と同じ範囲の 2^j 世代後が得られる
+        self.expand();
+        let root = self.root;
+        self.root = self.successor(root, j);
+        let quarter = 1i64 << (self.level() - 1);
+        self.origin = (self.origin.0 + quarter, self.origin.1 + quarter);
+        self.generation += 1 << j;
+
+        if MAX_NODES < self.nodes.len() {
+            self.collect_garbage();
+        }
+    }
+
+    fn level(&self) -> u8 {
+        self.nodes[self.root as usize].level
+    }
+
+    // rootを中心に置いた一回り大きいノードに置き換える
+    fn expand(&mut self) {
+        let level = self.level();
+        let empty = self.empty_node(level - 1);
+        let [nw, ne, sw, se] = self.nodes[self.root as usize].children;
+        let nw = self.join([empty, empty, empty, nw]);
+        let ne = self.join([empty, empty, ne, empty]);
+        let sw = self.join([empty, sw, empty, empty]);
+        let se = self.join([se, empty, empty, empty]);
+        self.root = self.join([nw, ne, sw, se]);
+        let half = 1i64 << (level - 1);
+        self.origin = (self.origin.0 - half, self.origin.1 - half);
+    }
+
+    // 生きたセルがすべて中心の1/4（子の子のうち内側の4つ）に収まっているかを返す
+    fn is_padded(&self, id: NodeId) -> bool {
+        let [nw, ne, sw, se] = self.nodes[id as usize].children;
+        let inner = [self.child(nw, 3), self.child(ne, 2), self.child(sw, 1), self.child(se, 0)];
+        let inner_population: u64 = inner.iter().map(|&id| self.nodes[id as usize].population).sum();
+        inner_population == self.nodes[id as usize].population
+    }
+
+    fn child(&self, id: NodeId, i: usize) -> NodeId {
+        self.nodes[id as usize].children[i]
+    }
+
+    // 4つの子からノードを作る（同じ内容のノードがあればそれを返す）
+    fn join(&mut self, children: [NodeId; 4]) -> NodeId {
+        if let Some(&id) = self.index.get(&children) {
+            return id;
+        }
+        let level = self.nodes[children[0] as usize].level + 1;
+        let population = children.iter().map(|&id| self.nodes[id as usize].population).sum();
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(Node { level, children, population });
+        self.index.insert(children, id);
+        id
+    }
+
+    // 一辺 2^level の空のノード
+    fn empty_node(&mut self, level: u8) -> NodeId {
+        while self.empty.len() <= level as usize {
+            let e = *self.empty.last().unwrap();
+            let id = self.join([e, e, e, e]);
+            self.empty.push(id);
+        }
+        self.empty[level as usize]
+    }
+
+    // レベルkのノードの中心にある、レベルk-1のノードを返す（世代は進めない）
+    fn center(&mut self, id: NodeId) -> NodeId {
+        let [nw, ne, sw, se] = self.nodes[id as usize].children;
+        let children = [self.child(nw, 3), self.child(ne, 2), self.child(sw, 1), self.child(se, 0)];
+        self.join(children)
+    }
+
+    // レベルkのノードの中心にあるレベルk-1のノードを 2^j 世代進めた結果を返す（j <= k-2）
+    fn successor(&mut self, id: NodeId, j: u8) -> NodeId {
+        let node = self.nodes[id as usize];
+        if node.population == 0 {
+            return self.empty_node(node.level - 1);
+        }
+        if node.level == 2 {
+            return self.successor_of_4x4(id);
+        }
+        if let Some(&result) = self.memo.get(&(id, j)) {
+            return result;
+        }
+
+        // 一辺を4分割したレベルk-2のノード g[y][x] を並べ、重なり合う9つのレベルk-1のノードを作る
+        let [nw, ne, sw, se] = node.children;
+        let g = [
+            [self.child(nw, 0), self.child(nw, 1), self.child(ne, 0), self.child(ne, 1)],
+            [self.child(nw, 2), self.child(nw, 3), self.child(ne, 2), self.child(ne, 3)],
+            [self.child(sw, 0), self.child(sw, 1), self.child(se, 0), self.child(se, 1)],
+            [self.child(sw, 2), self.child(sw, 3), self.child(se, 2), self.child(se, 3)],
+        ];
+        let full_speed = j + 2 == node.level;
+        let mut parts = [[DEAD; 3]; 3];
+        for (y, row) in parts.iter_mut().enumerate() {
+            for (x, part) in row.iter_mut().enumerate() {
+                let sub = self.join([g[y][x], g[y][x + 1], g[y + 1][x], g[y + 1][x + 1]]);
+                // 最大速度なら前半の 2^(k-3) 世代をここで進め、そうでなければ中心を取り出すだけ
+                *part = if full_speed { self.successor(sub, j - 1) } else { self.center(sub) };
+            }
+        }
+
+        // 9つの結果から4つのレベルk-1のノードを作り、残りの世代を進める
+        let remaining = if full_speed { j - 1 } else { j };
+        let mut quadrants = [DEAD; 4];
+        for (i, quadrant) in quadrants.iter_mut().enumerate() {
+            let (x, y) = (i % 2, i / 2);
+            let sub = self.join([parts[y][x], parts[y][x + 1], parts[y + 1][x], parts[y + 1][x + 1]]);
+            *quadrant = self.successor(sub, remaining);
+        }
+        let result = self.join(quadrants);
+        self.memo.insert((id, j), result);
+        result
+    }
+
+    // 4x4のノードの中心2x2を1世代進めた結果をルールに従って直接計算する
+    fn successor_of_4x4(&mut self, id: NodeId) -> NodeId {
+        let mut cells = [[false; 4]; 4];
+        let [nw, ne, sw, se] = self.nodes[id as usize].children;
+        for (i, &quadrant) in [nw, ne, sw, se].iter().enumerate() {
+            for (k, &leaf) in self.nodes[quadrant as usize].children.iter().enumerate() {
+                let x = (i % 2) * 2 + k % 2;
+                let y = (i / 2) * 2 + k / 2;
+                cells[y][x] = leaf == ALIVE;
+            }
+        }
+        let mut next = [DEAD; 4];
+        for (k, leaf) in next.iter_mut().enumerate() {
+            let (x, y) = (1 + k % 2, 1 + k / 2);
+            let block: u32 = cells[y - 1..=y + 1].iter()
+                .map(|row| row[x - 1..=x + 1].iter().filter(|&&alive| alive).count() as u32)
+                .sum();
+            let count = block - cells[y][x] as u32;
+            let state = if cells[y][x] { CellState::Alive } else { CellState::Dead };
+            if self.rule.next_state(state, count) == CellState::Alive {
+                *leaf = ALIVE;
+            }
+        }
+        self.join(next)
+    }
+
+    // rootから辿れるノードだけを残して作り直す
+    fn collect_garbage(&mut self) {
+        let mut fresh = HashLife::new(self.rule);
+        let mut copied = HashMap::new();
+        fresh.root = fresh.copy_from(self, self.root, &mut copied);
+        fresh.origin = self.origin;
+        fresh.generation = self.generation;
+        *self = fresh;
+    }
+
+    fn copy_from(&mut self, other: &HashLife, id: NodeId, copied: &mut HashMap<NodeId, NodeId>) -> NodeId {
+        if id == DEAD || id == ALIVE {
+            return id;
+        }
+        if let Some(&new_id) = copied.get(&id) {
+            return new_id;
+        }
+        let children = other.nodes[id as usize].children;
+        let children = children.map(|child| self.copy_from(other, child, copied));
+        let new_id = self.join(children);
+        copied.insert(id, new_id);
+        new_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    // 中央に size x size のランダムなスープを置いた、十分に広いフィールドを作る
+    fn soup_field(rule: Rule, seed: u64, size: usize) -> Field {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut field = Field::new(192, 192, 0.0);
+        field.rule = rule;
+        for x in 0..size {
+            for y in 0..size {
+                if rng.gen_bool(0.4) {
+                    field.matrix[96 - size / 2 + x][96 - size / 2 + y].state = CellState::Alive;
+                }
+            }
+        }
+        field
+    }
+
+    fn states(field: &Field) -> Vec<CellState> {
+        field.matrix.iter().flatten().map(|cell| cell.state).collect()
+    }
+
+    #[test]
+    fn agrees_with_dense_field_on_random_soups() {
+        // 64世代では光速で広がっても端に届かないので、無限平面と同じ結果になる
+        for (seed, rule) in [(1, Rule::conway()), (2, Rule::conway()), (3, Rule::preset(1).unwrap())] {
+            let mut dense = soup_field(rule, seed, 24);
+            let mut hashlife = HashLife::from_field(&dense);
+            for _ in 0..64 {
+                dense.iterate();
+            }
+            hashlife.step(6);
+            assert_eq!(hashlife.generation, 64);
+
+            let mut converted = Field::new(192, 192, 0.0);
+            hashlife.write_to(&mut converted);
+            assert_eq!(states(&converted), states(&dense), "seed {}", seed);
+            assert_eq!(hashlife.population() as usize, states(&dense).iter().filter(|&&s| s == CellState::Alive).count());
+        }
+    }
+
+    #[test]
+    fn small_steps_match_one_big_jump() {
+        let field = soup_field(Rule::conway(), 7, 16);
+        let mut stepped = HashLife::from_field(&field);
+        for _ in 0..32 {
+            stepped.step(0);
+        }
+        let mut jumped = HashLife::from_field(&field);
+        jumped.step(5);
+
+        let mut a = Field::new(192, 192, 0.0);
+        let mut b = Field::new(192, 192, 0.0);
+        stepped.write_to(&mut a);
+        jumped.write_to(&mut b);
+        assert_eq!(states(&a), states(&b));
+        assert_eq!(stepped.generation, jumped.generation);
+    }
+
+    #[test]
+    fn glider_travels_far_beyond_the_field() {
+        let mut field = Field::new(8, 8, 0.0);
+        for (x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            field.matrix[x][y].state = CellState::Alive;
+        }
+        let mut hashlife = HashLife::from_field(&field);
+        hashlife.step(20);
+        assert_eq!(hashlife.population(), 5);
+        assert_eq!(hashlife.generation, 1 << 20);
+
+        // フィールドの範囲からは出ていってしまう
+        hashlife.write_to(&mut field);
+        assert!(states(&field).iter().all(|&state| state == CellState::Dead));
+    }
+
+    #[test]
+    fn rejects_unsupported_rules() {
+        assert!(HashLife::supports(&Rule::conway()));
+        assert!(!HashLife::supports(&Rule::preset(4).unwrap()));
+        assert!(!HashLife::supports(&Rule::preset(6).unwrap()));
+        assert!(!HashLife::supports(&"B0/S8".parse().unwrap()));
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::VecDeque;
+
+use crate::{Cell, CellState, Field, NEVER_ALIVE};
+
+// フィールドの過去の状態を、世代ごとの差分として一定数だけ覚えておくための構造体
+// フィールドは常に cursor の位置の状態を表し、差分を前後に当てることで過去と未来を行き来する
+pub struct History {
+    capacity: usize,          // 覚えておく差分の最大数。超えたら古いものから捨てる
+    entries: VecDeque<Entry>, // entries[i] は i 番目の状態から i+1 番目の状態への差分
+    cursor: usize,            // フィールドがいま表している状態の番号（0 が最も古い状態）
+    snapshot: Vec<Cell>,      // cursor の位置の状態（次の差分を求めるための比較元）
+}
+
+// ある状態から次の状態への差分
+struct Entry {
+    generations: u64,     // この差分で進んだ世代数（セルを編集しただけなら0）
+    changes: Vec<Change>, // 状態が変わったセル
+}
+
+// 1セルぶんの変化。前後両方のセル（状態・色・年齢）を持つので、どちら向きにも当てられる
+// 世代を進めた差分では、状態が変わらずに1世代ぶん年を取っただけのセルは記録しない
+#[derive(Clone, Copy)]
+struct Change {
+    index: u32,   // セルの番号（x * row_n + y）
+    before: Cell, // 変わる前のセル
+    after: Cell,  // 変わった後のセル
+}
+
+impl History {
+    // フィールドの現在の状態を起点に、capacity個までの差分を覚える履歴を作るためのメソッド
+    pub fn new(field: &Field, capacity: usize) -> History {
+        History { capacity, entries: VecDeque::new(), cursor: 0, snapshot: snapshot(field) }
+    }
+
+    // 覚えている差分の数（いちばん新しい状態の番号）
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    // 差分を一つも覚えていないかどうか
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // フィールドがいま表している状態の番号
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    // 過去に戻っていて、その先の状態を覚えているかどうか
+    pub fn is_rewound(&self) -> bool {
+        self.cursor < self.entries.len()
+    }
+
+    // フィールドの現在の状態を、前回からgenerations世代進んだ（0なら編集された）状態として記録するためのメソッド
+    // 過去に戻った状態から記録すると、その先の状態は捨てられ、そこから新しい流れが始まる
+    pub fn record(&mut self, field: &Field, generations: u64) {
+        let current = snapshot(field);
+        let aged = 0 < generations;
+        let changes: Vec<Change> = self.snapshot.iter().zip(&current).enumerate()
+            .filter(|(_, (&before, &after))| {
+                // 世代を進めた差分では、年を取っただけのセルは前後どちらへも年齢と死んでからの世代数を1ずらすだけで戻せる
+                if aged {
+                    age(before) != after || unage(after) != Some(before)
+                } else {
+                    before != after
+                }
+            })
+            .map(|(index, (&before, &after))| Change { index: index as u32, before, after })
+            .collect();
+        self.snapshot = current;
+        if changes.is_empty() && generations == 0 {
+            return;
+        }
+        self.entries.truncate(self.cursor);
+        self.entries.push_back(Entry { generations, changes });
+        if self.capacity < self.entries.len() {
+            self.entries.pop_front();
+        }
+        self.cursor = self.entries.len();
+    }
+
+    // フィールドを1つ前の状態に戻すためのメソッド。戻った世代数を返す（戻れなければNone）
+    pub fn step_back(&mut self, field: &mut Field) -> Option<u64> {
+        if self.cursor == 0 {
+            return None;
+        }
+        self.cursor -= 1;
+        let entry = &self.entries[self.cursor];
+        if 0 < entry.generations {
+            update_all(field, &mut self.snapshot, |cell| unage(cell).unwrap_or(cell));
+        }
+        for change in &entry.changes {
+            set_cell(field, &mut self.snapshot, change.index, change.before);
+        }
+        Some(entry.generations)
+    }
+
+    // フィールドを1つ後の状態に進めるためのメソッド。進んだ世代数を返す（進めなければNone）
+    pub fn step_forward(&mut self, field: &mut Field) -> Option<u64> {
+        let entry = self.entries.get(self.cursor)?;
+        if 0 < entry.generations {
+            update_all(field, &mut self.snapshot, age);
+        }
+        for change in &entry.changes {
+            set_cell(field, &mut self.snapshot, change.index, change.after);
+        }
+        self.cursor += 1;
+        Some(entry.generations)
+    }
+
+    // フィールドをcursor番目の状態にするためのメソッド。進んだ世代数（戻ったなら負の数）を返す
+    pub fn seek(&mut self, field: &mut Field, cursor: usize) -> i64 {
+        let cursor = cursor.min(self.entries.len());
+        let mut generations = 0i64;
+        while cursor < self.cursor {
+            generations -= self.step_back(field).unwrap() as i64;
+        }
+        while self.cursor < cursor {
+            generations += self.step_forward(field).unwrap() as i64;
+        }
+        generations
+    }
+}
+
+// フィールドのセルを、セルの番号の順に並べて返す
+fn snapshot(field: &Field) -> Vec<Cell> {
+    field.matrix.iter().flatten().copied().collect()
+}
+
+// 状態が変わらないまま1世代進んだセル
+fn age(cell: Cell) -> Cell {
+    cell.next_clone(cell.state)
+}
+
+// ageで1世代進める前のセル。年齢や死んでからの世代数が0で戻せなければNoneを返す
+fn unage(cell: Cell) -> Option<Cell> {
+    let mut previous = cell;
+    if cell.state == CellState::Alive {
+        previous.age = cell.age.checked_sub(1)?;
+    } else if cell.dead_for != NEVER_ALIVE {
+        previous.dead_for = cell.dead_for.checked_sub(1)?;
+    }
+    Some(previous)
+}
+
+// 番号indexのセルを、フィールドとスナップショットの両方で書き換える
+fn set_cell(field: &mut Field, snapshot: &mut [Cell], index: u32, cell: Cell) {
+    let row_n = field.row_n as usize;
+    let index = index as usize;
+    field.matrix[index / row_n][index % row_n] = cell;
+    snapshot[index] = cell;
+}
+
+// すべてのセルを、フィールドとスナップショットの両方でupdateの結果に書き換える
+fn update_all(field: &mut Field, snapshot: &mut [Cell], update: impl Fn(Cell) -> Cell) {
+    for (cell, snapshot) in field.matrix.iter_mut().flatten().zip(snapshot.iter_mut()) {
+        *cell = update(*cell);
+        *snapshot = *cell;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary::Boundary;
+    use crate::colour::ColourMode;
+    use rand::prelude::*;
+
+    fn soup_field(seed: u64) -> Field {
+        let mut field = Field::new(20, 30, 0.0);
+        field.boundary = Boundary::Torus;
+        let mut rng = StdRng::seed_from_u64(seed);
+        for cell in field.matrix.iter_mut().flatten() {
+            if rng.gen_bool(0.4) {
+                cell.state = CellState::Alive;
+            }
+        }
+        field
+    }
+
+    #[test]
+    fn rewinds_and_replays_every_generation() {
+        let mut field = soup_field(1);
+        let mut history = History::new(&field, 100);
+        let mut states = vec![snapshot(&field)];
+        for _ in 0..40 {
+            field.iterate();
+            history.record(&field, 1);
+            states.push(snapshot(&field));
+        }
+        for cursor in (0..40).rev() {
+            assert_eq!(history.step_back(&mut field), Some(1));
+            assert_eq!(snapshot(&field), states[cursor]);
+        }
+        assert_eq!(history.step_back(&mut field), None);
+        assert_eq!(history.seek(&mut field, 40), 40);
+        assert_eq!(snapshot(&field), states[40]);
+        assert_eq!(history.seek(&mut field, 15), -25);
+        assert_eq!(snapshot(&field), states[15]);
+    }
+
+    #[test]
+    fn restores_colours_and_ages_along_with_states() {
+        let mut field = soup_field(4);
+        field.colour_mode = ColourMode::Immigration;
+        field.recolour(4);
+        let mut history = History::new(&field, 100);
+        let mut cells = vec![snapshot(&field)];
+        for _ in 0..20 {
+            field.iterate();
+            history.record(&field, 1);
+            cells.push(snapshot(&field));
+        }
+        // 手で塗り替えたセルも、色ごと元に戻る
+        field.matrix[3][3] = Cell::new(CellState::Alive, 1.0, 1.0, 1.0);
+        history.record(&field, 0);
+        cells.push(snapshot(&field));
+        assert_eq!(history.seek(&mut field, 5), -15);
+        assert_eq!(snapshot(&field), cells[5]);
+        assert_eq!(history.seek(&mut field, 21), 15);
+        assert_eq!(snapshot(&field), cells[21]);
+    }
+
+    #[test]
+    fn does_not_record_cells_that_only_grew_older() {
+        // ブロックは生き続けるだけなので、どの世代の差分にもセルが記録されない
+        let mut field = Field::new(6, 6, 0.0);
+        for (x, y) in [(2, 2), (2, 3), (3, 2), (3, 3)] {
+            field.matrix[x][y] = field.matrix[x][y].alive_clone();
+        }
+        let start = snapshot(&field);
+        let mut history = History::new(&field, 100);
+        for _ in 0..5 {
+            field.iterate();
+            history.record(&field, 1);
+        }
+        assert!(history.entries.iter().all(|entry| entry.changes.is_empty()));
+        assert_eq!(field.matrix[2][2].age, 5);
+        assert_eq!(history.seek(&mut field, 0), -5);
+        assert_eq!(snapshot(&field), start);
+    }
+
+    #[test]
+    fn forgets_the_oldest_generations_beyond_capacity() {
+        let mut field = soup_field(2);
+        let mut history = History::new(&field, 10);
+        let mut states = vec![snapshot(&field)];
+        for _ in 0..25 {
+            field.iterate();
+            history.record(&field, 1);
+            states.push(snapshot(&field));
+        }
+        assert_eq!(history.len(), 10);
+        assert_eq!(history.seek(&mut field, 0), -10);
+        assert_eq!(snapshot(&field), states[15]);
+    }
+
+    #[test]
+    fn recording_in_the_past_forks_a_new_run() {
+        let mut field = soup_field(3);
+        let mut history = History::new(&field, 100);
+        for _ in 0..10 {
+            field.iterate();
+            history.record(&field, 1);
+        }
+        history.seek(&mut field, 4);
+        assert!(history.is_rewound());
+
+        // 過去の状態を編集してから進めると、それより先の状態は捨てられる
+        field.matrix[0][0].state = CellState::Alive;
+        field.matrix[1][0].state = CellState::Dead;
+        history.record(&field, 0);
+        field.iterate();
+        history.record(&field, 1);
+        assert!(!history.is_rewound());
+        assert_eq!(history.len(), 6);
+
+        let forked = snapshot(&field);
+        assert_eq!(history.step_back(&mut field), Some(1));
+        assert_eq!(history.step_back(&mut field), Some(0));
+        history.seek(&mut field, 6);
+        assert_eq!(snapshot(&field), forked);
+    }
+}
@@ -0,0 +1,363 @@
+use std::sync::Arc;
+
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+// Lenia（連続値のセル・リング状の畳み込みカーネル・成長関数によるセル・オートマトン）のパラメータ
+#[derive(Clone, PartialEq, Debug)]
+pub struct Params {
+    pub radius: f32,     // カーネルの半径R（セル数）
+    pub time_steps: f32, // 単位時間あたりのステップ数T（1ステップで進める時間は1/T）
+    pub peaks: Vec<f32>, // カーネルの同心円状のリングの高さ（内側から順に）
+    pub mu: f32,         // 成長関数が最大になる、近傍の重み付き平均の値
+    pub sigma: f32,      // 成長関数の幅
+}
+
+// 組み込みの生き物（プリセット）
+pub struct Preset {
+    pub name: &'static str,               // 名前
+    pub radius: f32,                      // カーネルの半径R
+    pub time_steps: f32,                  // 単位時間あたりのステップ数T
+    pub peaks: &'static [f32],            // カーネルのリングの高さ
+    pub mu: f32,                          // 成長関数の中心
+    pub sigma: f32,                       // 成長関数の幅
+    pub cells: &'static [&'static [f32]], // 生き物のセルの値（行ごと）。空なら乱数で生成したスープから始める
+    pub placements: &'static [Placement], // 生き物を置く場所と向き
+}
+
+// 生き物を置く場所と向き
+pub struct Placement {
+    pub x: f32,            // 生き物の中心のx座標（フィールドの幅に対する割合）
+    pub y: f32,            // 生き物の中心のy座標（フィールドの高さに対する割合）
+    pub quarter_turns: u8, // 時計回りに90度回転させる回数
+}
+
+// フィールドの中央にそのままの向きで置く
+const CENTRE: [Placement; 1] = [Placement { x: 0.5, y: 0.5, quarter_turns: 0 }];
+
+impl Preset {
+    // プリセットのパラメータ
+    pub fn params(&self) -> Params {
+        Params { radius: self.radius, time_steps: self.time_steps, peaks: self.peaks.to_vec(), mu: self.mu, sigma: self.sigma }
+    }
+}
+
+// 乱数で生成するスープの大きさ（カーネルの半径の何倍の正方形にするか）
+const SOUP_SIZE_IN_RADII: f32 = 6.0;
+
+// プリセット。数字キーで選ぶ
+// OrbiumのセルはBert ChanによるLeniaの論文とチュートリアルで公開されているもの
+// 2匹のプリセットは256x256のフィールドで3000ステップ確かめたもの
+pub const PRESETS: [Preset; 4] = [
+    Preset { name: "Orbium", radius: 13.0, time_steps: 10.0, peaks: &[1.0], mu: 0.15, sigma: 0.015, cells: &ORBIUM, placements: &CENTRE },
+    // 反対向きに進む2匹がすれ違う
+    Preset {
+        name: "Orbium pair",
+        radius: 13.0,
+        time_steps: 10.0,
+        peaks: &[1.0],
+        mu: 0.15,
+        sigma: 0.015,
+        cells: &ORBIUM,
+        placements: &[Placement { x: 0.3, y: 0.5, quarter_turns: 1 }, Placement { x: 0.7, y: 0.5, quarter_turns: 3 }],
+    },
+    // 直角に進む2匹がぶつかり、片方がもう片方を飲み込む
+    Preset {
+        name: "Orbium collision",
+        radius: 13.0,
+        time_steps: 10.0,
+        peaks: &[1.0],
+        mu: 0.15,
+        sigma: 0.015,
+        cells: &ORBIUM,
+        placements: &[Placement { x: 0.25, y: 0.5, quarter_turns: 0 }, Placement { x: 0.75, y: 0.5, quarter_turns: 1 }],
+    },
+    // Hydrogeminiumのパラメータで、乱数のスープから模様が育つ様子を見る
+    Preset {
+        name: "Hydrogeminium soup",
+        radius: 18.0,
+        time_steps: 10.0,
+        peaks: &[0.5, 1.0, 2.0 / 3.0],
+        mu: 0.26,
+        sigma: 0.036,
+        cells: &[],
+        placements: &[],
+    },
+];
+
+const ORBIUM: [&[f32]; 20] = [
+    &[0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.1, 0.14, 0.1, 0.0, 0.0, 0.03, 0.03, 0.0, 0.0, 0.3, 0.0, 0.0, 0.0, 0.0],
+    &[0.0, 0.0, 0.0, 0.0, 0.0, 0.08, 0.24, 0.3, 0.3, 0.18, 0.14, 0.15, 0.16, 0.15, 0.09, 0.2, 0.0, 0.0, 0.0, 0.0],
+    &[0.0, 0.0, 0.0, 0.0, 0.0, 0.15, 0.34, 0.44, 0.46, 0.38, 0.18, 0.14, 0.11, 0.13, 0.19, 0.18, 0.45, 0.0, 0.0, 0.0],
+    &[0.0, 0.0, 0.0, 0.0, 0.06, 0.13, 0.39, 0.5, 0.5, 0.37, 0.06, 0.0, 0.0, 0.0, 0.02, 0.16, 0.68, 0.0, 0.0, 0.0],
+    &[0.0, 0.0, 0.0, 0.11, 0.17, 0.17, 0.33, 0.4, 0.38, 0.28, 0.14, 0.0, 0.0, 0.0, 0.0, 0.0, 0.18, 0.42, 0.0, 0.0],
+    &[0.0, 0.0, 0.09, 0.18, 0.13, 0.06, 0.08, 0.26, 0.32, 0.32, 0.27, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.82, 0.0, 0.0],
+    &[0.27, 0.0, 0.16, 0.12, 0.0, 0.0, 0.0, 0.25, 0.38, 0.44, 0.45, 0.34, 0.0, 0.0, 0.0, 0.0, 0.0, 0.22, 0.17, 0.0],
+    &[0.0, 0.07, 0.2, 0.02, 0.0, 0.0, 0.0, 0.31, 0.48, 0.57, 0.6, 0.57, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.49, 0.0],
+    &[0.0, 0.59, 0.19, 0.0, 0.0, 0.0, 0.0, 0.2, 0.57, 0.69, 0.76, 0.76, 0.49, 0.0, 0.0, 0.0, 0.0, 0.0, 0.36, 0.0],
+    &[0.0, 0.58, 0.19, 0.0, 0.0, 0.0, 0.0, 0.0, 0.67, 0.83, 0.9, 0.92, 0.87, 0.12, 0.0, 0.0, 0.0, 0.0, 0.22, 0.07],
+    &[0.0, 0.0, 0.46, 0.0, 0.0, 0.0, 0.0, 0.0, 0.7, 0.93, 1.0, 1.0, 1.0, 0.61, 0.0, 0.0, 0.0, 0.0, 0.18, 0.11],
+    &[0.0, 0.0, 0.82, 0.0, 0.0, 0.0, 0.0, 0.0, 0.47, 1.0, 1.0, 0.98, 1.0, 0.96, 0.27, 0.0, 0.0, 0.0, 0.19, 0.1],
+    &[0.0, 0.0, 0.46, 0.0, 0.0, 0.0, 0.0, 0.0, 0.25, 1.0, 1.0, 0.84, 0.92, 0.97, 0.54, 0.14, 0.04, 0.1, 0.21, 0.05],
+    &[0.0, 0.0, 0.0, 0.4, 0.0, 0.0, 0.0, 0.0, 0.09, 0.8, 1.0, 0.82, 0.8, 0.85, 0.63, 0.31, 0.18, 0.19, 0.2, 0.01],
+    &[0.0, 0.0, 0.0, 0.36, 0.1, 0.0, 0.0, 0.0, 0.05, 0.54, 0.86, 0.79, 0.74, 0.72, 0.6, 0.39, 0.28, 0.24, 0.13, 0.0],
+    &[0.0, 0.0, 0.0, 0.01, 0.3, 0.07, 0.0, 0.0, 0.08, 0.36, 0.64, 0.7, 0.64, 0.6, 0.51, 0.39, 0.29, 0.19, 0.04, 0.0],
+    &[0.0, 0.0, 0.0, 0.0, 0.1, 0.24, 0.14, 0.1, 0.15, 0.29, 0.45, 0.53, 0.52, 0.46, 0.4, 0.31, 0.21, 0.08, 0.0, 0.0],
+    &[0.0, 0.0, 0.0, 0.0, 0.0, 0.08, 0.21, 0.21, 0.22, 0.29, 0.36, 0.39, 0.37, 0.33, 0.26, 0.18, 0.09, 0.0, 0.0, 0.0],
+    &[0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.03, 0.13, 0.19, 0.22, 0.24, 0.24, 0.23, 0.18, 0.13, 0.05, 0.0, 0.0, 0.0, 0.0],
+    &[0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.02, 0.06, 0.08, 0.09, 0.07, 0.05, 0.01, 0.0, 0.0, 0.0, 0.0, 0.0],
+];
+
+// 値を色に変える色の変化（0の暗い青から、青・水色・黄色を経て、1の赤へ）
+const COLOUR_MAP: [[f32; 3]; 5] = [[0.05, 0.05, 0.15], [0.1, 0.2, 0.7], [0.1, 0.8, 0.9], [1.0, 0.9, 0.2], [0.9, 0.1, 0.1]];
+
+// 端がつながった（トーラス状の）フィールドの上のLenia
+// 畳み込みは高速フーリエ変換（FFT）で計算するので、1ステップの計算量はセル数Nに対してN log Nになる
+pub struct Lenia {
+    pub width: usize,              // フィールドの列数
+    pub height: usize,             // フィールドの行数
+    pub cells: Vec<f32>,           // セルの値（0〜1）。y * width + x 番目が(x, y)のセル
+    pub params: Params,            // カーネルと成長関数のパラメータ
+    pub steps: u64,                // 進めたステップ数
+    kernel: Vec<Complex<f32>>,     // 正規化したカーネルをFFTで変換したもの（列ごとに並べ替えた順）
+    row_fft: Arc<dyn Fft<f32>>,    // 1行（width個）のFFT
+    row_ifft: Arc<dyn Fft<f32>>,   // 1行の逆FFT
+    col_fft: Arc<dyn Fft<f32>>,    // 1列（height個）のFFT
+    col_ifft: Arc<dyn Fft<f32>>,   // 1列の逆FFT
+    buffer: Vec<Complex<f32>>,     // 行ごとに並べた作業用の配列
+    transposed: Vec<Complex<f32>>, // 列ごとに並べた作業用の配列
+}
+
+impl Lenia {
+    // すべてのセルが0のフィールドを作るためのメソッド
+    pub fn new(width: usize, height: usize, params: Params) -> Lenia {
+        let mut planner = FftPlanner::new();
+        let mut lenia = Lenia {
+            width,
+            height,
+            cells: vec![0.0; width * height],
+            params,
+            steps: 0,
+            kernel: vec![],
+            row_fft: planner.plan_fft_forward(width),
+            row_ifft: planner.plan_fft_inverse(width),
+            col_fft: planner.plan_fft_forward(height),
+            col_ifft: planner.plan_fft_inverse(height),
+            buffer: vec![Complex::default(); width * height],
+            transposed: vec![Complex::default(); width * height],
+        };
+        lenia.kernel = lenia.kernel_spectrum();
+        lenia
+    }
+
+    // プリセットの生き物を置いた（またはスープを生成した）フィールドを作るためのメソッド
+    pub fn from_preset(width: usize, height: usize, preset: &Preset, seed: u64) -> Lenia {
+        let mut lenia = Lenia::new(width, height, preset.params());
+        if preset.cells.is_empty() {
+            lenia.seed_soup(seed);
+        }
+        for placement in preset.placements {
+            lenia.place(preset.cells, placement);
+        }
+        lenia
+    }
+
+    // 行ごとの値cellsを、placementの場所と向きで置くためのメソッド
+    pub fn place(&mut self, cells: &[&[f32]], placement: &Placement) {
+        let (rows, cols) = (cells.len() as i64, cells.first().map_or(0, |row| row.len()) as i64);
+        let centre_x = (placement.x * self.width as f32) as i64;
+        let centre_y = (placement.y * self.height as f32) as i64;
+        for (row, values) in cells.iter().enumerate() {
+            for (col, &value) in values.iter().enumerate() {
+                // 中心からの位置を時計回りに回転させる
+                let (mut dx, mut dy) = (col as i64 - cols / 2, row as i64 - rows / 2);
+                for _ in 0..placement.quarter_turns % 4 {
+                    (dx, dy) = (-dy, dx);
+                }
+                let x = (centre_x + dx).rem_euclid(self.width as i64) as usize;
+                let y = (centre_y + dy).rem_euclid(self.height as i64) as usize;
+                self.cells[y * self.width + x] = value;
+            }
+        }
+    }
+
+    // 中央の正方形の範囲に、乱数の種seedから一様乱数の値を置くためのメソッド
+    pub fn seed_soup(&mut self, seed: u64) {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let size = ((self.params.radius * SOUP_SIZE_IN_RADII) as usize).min(self.width).min(self.height);
+        let (left, top) = ((self.width - size) / 2, (self.height - size) / 2);
+        for y in top..top + size {
+            for x in left..left + size {
+                self.cells[y * self.width + x] = rng.gen();
+            }
+        }
+    }
+
+    // セルの値の合計（生き物の質量）
+    pub fn mass(&self) -> f32 {
+        self.cells.iter().sum()
+    }
+
+    // 1ステップ進めるためのメソッド
+    // 各セルの近傍の重み付き平均uをカーネルとの畳み込みで求め、成長関数の値の1/Tだけセルの値を増減させる
+    pub fn step(&mut self) {
+        for (value, &cell) in self.buffer.iter_mut().zip(&self.cells) {
+            *value = Complex::new(cell, 0.0);
+        }
+        self.forward();
+        for (value, kernel) in self.transposed.iter_mut().zip(&self.kernel) {
+            *value *= kernel;
+        }
+        self.inverse();
+
+        let scale = 1.0 / (self.width * self.height) as f32;
+        let dt = 1.0 / self.params.time_steps;
+        for (cell, value) in self.cells.iter_mut().zip(&self.buffer) {
+            let growth = growth(value.re * scale, self.params.mu, self.params.sigma);
+            *cell = (*cell + dt * growth).clamp(0.0, 1.0);
+        }
+        self.steps += 1;
+    }
+
+    // セルの値を色に変えた、行ごとに並べたRGBAの画素（1画素あたり4バイト）を返すメソッド
+    pub fn to_rgba(&self) -> Vec<u8> {
+        self.cells.iter()
+            .flat_map(|&value| {
+                let [r, g, b] = colour_map(value);
+                [r, g, b, 1.0].map(|channel| (channel * 255.0).round() as u8)
+            })
+            .collect()
+    }
+
+    // カーネルを作り、合計が1になるように正規化してからFFTで変換したものを返すメソッド
+    // カーネルの中心は(0, 0)に置き、負の座標は反対側の端に回り込ませる
+    fn kernel_spectrum(&mut self) -> Vec<Complex<f32>> {
+        let radius = self.params.radius;
+        let reach = radius.ceil() as i64;
+        let mut kernel = vec![0.0f32; self.width * self.height];
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let distance = ((dx * dx + dy * dy) as f32).sqrt() / radius;
+                let x = dx.rem_euclid(self.width as i64) as usize;
+                let y = dy.rem_euclid(self.height as i64) as usize;
+                kernel[y * self.width + x] += shell(distance, &self.params.peaks);
+            }
+        }
+        let total: f32 = kernel.iter().sum();
+        for (value, weight) in self.buffer.iter_mut().zip(&kernel) {
+            *value = Complex::new(weight / total, 0.0);
+        }
+        self.forward();
+        self.transposed.clone()
+    }
+
+    // bufferを2次元FFTで変換し、結果を列ごとに並べた順でtransposedに入れる
+    fn forward(&mut self) {
+        self.row_fft.process(&mut self.buffer);
+        transpose(&self.buffer, &mut self.transposed, self.width, self.height);
+        self.col_fft.process(&mut self.transposed);
+    }
+
+    // 列ごとに並べたtransposedを2次元逆FFTで変換し、結果を行ごとに並べた順でbufferに入れる（正規化はしない）
+    fn inverse(&mut self) {
+        self.col_ifft.process(&mut self.transposed);
+        transpose(&self.transposed, &mut self.buffer, self.height, self.width);
+        self.row_ifft.process(&mut self.buffer);
+    }
+}
+
+// 中心からの距離distance（半径を1とする）でのカーネルの値
+// 半径をリングの数に等分し、それぞれのリングの中で山型（両端で0、中央で1）になる関数にリングの高さを掛ける
+fn shell(distance: f32, peaks: &[f32]) -> f32 {
+    if distance >= 1.0 {
+        return 0.0;
+    }
+    let position = distance * peaks.len() as f32;
+    let ring = position as usize;
+    let x = position - ring as f32;
+    if x <= 0.0 {
+        return 0.0;
+    }
+    peaks[ring] * (4.0 - 1.0 / (x * (1.0 - x))).exp()
+}
+
+// 近傍の重み付き平均uに対する成長関数の値（-1〜1）。uがmuに近いほど大きい
+fn growth(u: f32, mu: f32, sigma: f32) -> f32 {
+    2.0 * (-(u - mu) * (u - mu) / (2.0 * sigma * sigma)).exp() - 1.0
+}
+
+// セルの値（0〜1）を、COLOUR_MAPの色の間を補間した色に変える
+pub fn colour_map(value: f32) -> [f32; 3] {
+    let position = value.clamp(0.0, 1.0) * (COLOUR_MAP.len() - 1) as f32;
+    let i = (position as usize).min(COLOUR_MAP.len() - 2);
+    let t = position - i as f32;
+    let (from, to) = (COLOUR_MAP[i], COLOUR_MAP[i + 1]);
+    [0, 1, 2].map(|channel| from[channel] + (to[channel] - from[channel]) * t)
+}
+
+// width列height行に並んだ値を、height列width行に並べ替える
+fn transpose(from: &[Complex<f32>], to: &mut [Complex<f32>], width: usize, height: usize) {
+    for y in 0..height {
+        for x in 0..width {
+            to[x * height + y] = from[y * width + x];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 質量の重心（端がつながっていることは考えない）
+    fn centroid(lenia: &Lenia) -> (f32, f32) {
+        let (mut sx, mut sy) = (0.0, 0.0);
+        for (i, &value) in lenia.cells.iter().enumerate() {
+            sx += (i % lenia.width) as f32 * value;
+            sy += (i / lenia.width) as f32 * value;
+        }
+        (sx / lenia.mass(), sy / lenia.mass())
+    }
+
+    #[test]
+    fn orbium_glides_without_losing_mass() {
+        let mut lenia = Lenia::from_preset(128, 128, &PRESETS[0], 0);
+        let mass = lenia.mass();
+        let (x, y) = centroid(&lenia);
+        for _ in 0..60 {
+            lenia.step();
+            assert!((lenia.mass() - mass).abs() < mass * 0.1, "mass {} at step {}", lenia.mass(), lenia.steps);
+        }
+        // 60ステップで下に30セルほど進む
+        let (moved_x, moved_y) = centroid(&lenia);
+        assert!((moved_x - x).abs() < 20.0 && 20.0 < moved_y - y, "{:?} -> {:?}", (x, y), (moved_x, moved_y));
+    }
+
+    #[test]
+    fn the_kernel_is_normalised() {
+        // どこも同じ値muのフィールドでは、近傍の重み付き平均もmuになるので、すべてのセルが1/Tずつ増える
+        let params = PRESETS[3].params();
+        let mut lenia = Lenia::new(48, 40, params.clone());
+        lenia.cells.fill(params.mu);
+        lenia.step();
+        assert!(lenia.cells.iter().all(|&value| (value - (params.mu + 1.0 / params.time_steps)).abs() < 1e-4));
+
+        // すべてのセルが0のフィールドは0のまま
+        let mut empty = Lenia::new(32, 32, params);
+        empty.step();
+        assert_eq!(empty.mass(), 0.0);
+    }
+
+    #[test]
+    fn placements_rotate_clockwise() {
+        let mut lenia = Lenia::new(8, 8, PRESETS[0].params());
+        let arrow: [&[f32]; 2] = [&[0.0, 0.5, 0.0], &[0.0, 1.0, 0.0]];
+        lenia.place(&arrow, &Placement { x: 0.5, y: 0.5, quarter_turns: 1 });
+        // 中心(4, 4)の上にあった0.5が右に来る
+        assert_eq!((lenia.cells[4 * 8 + 5], lenia.cells[4 * 8 + 4]), (0.5, 1.0));
+        assert_eq!(lenia.mass(), 1.5);
+        assert_eq!(colour_map(0.0), COLOUR_MAP[0]);
+        assert_eq!(&lenia.to_rgba()[(4 * 8 + 5) * 4..][..4], &[26, 204, 230, 255]);
+    }
+}
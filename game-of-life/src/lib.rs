@@ -0,0 +1,20 @@
+pub mod args;
+pub mod bitfield;
+pub mod boundary;
+pub mod census;
+pub mod colour;
+pub mod field;
+pub mod hashlife;
+pub mod history;
+pub mod lenia;
+pub mod manifest;
+pub mod neighbourhood;
+pub mod pattern;
+pub mod period;
+pub mod render;
+pub mod rule;
+pub mod session;
+pub mod sparse;
+pub mod stats;
+
+pub use field::{Cell, CellState, Field, NEVER_ALIVE};
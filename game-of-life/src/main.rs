@@ -1,196 +1,1428 @@
+use std::time::Instant;
+
 use nannou::prelude::*;
-use rand::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use game_of_life::args::{arg_value, parse_arg};
+use game_of_life::bitfield::BitField;
+use game_of_life::colour::ColourMode;
+use game_of_life::hashlife::HashLife;
+use game_of_life::history::History;
+use game_of_life::lenia::{self, Lenia};
+use game_of_life::manifest::{BackendKind, Manifest};
+use game_of_life::neighbourhood::Neighbourhood;
+use game_of_life::pattern::{self, Pattern};
+use game_of_life::period::{Outcome, PeriodDetector};
+use game_of_life::render::{self, RenderMode};
+use game_of_life::rule::{self, Family, Rule};
+use game_of_life::session::Session;
+use game_of_life::sparse::SparseField;
+use game_of_life::stats::Stats;
+use game_of_life::{Cell, CellState, Field};
 
-const COL_N: u32 = 128;                      // フィールドの列数
-const ROW_N: u32 = 72;                       // フィールドの行数
-const CELL_SIZE: u32 = 8;                    // セル一つあたりの1辺の長さ（ポイント)
-const INITIAL_ALIVE_RATIO: f32 = 0.25;       // フィールド生成時に生存しているセルの割合
-const ITERATION_INTERVAL_SECONDS: f32 = 0.1; // フィールドの更新間隔（秒）
+const COL_N: u32 = 128;                                 // フィールドの列数
+const ROW_N: u32 = 72;                                  // フィールドの行数
+const CELL_SIZE: f32 = 8.0;                             // セル一つあたりの1辺の長さ（ポイント）の初期値（--cell-size オプションで変更できる）
+const INITIAL_ALIVE_RATIO: f32 = 0.25;                  // フィールド生成時に生存しているセルの割合
+const ITERATION_INTERVAL_SECONDS: f32 = 0.1;            // フィールドの更新間隔（秒）
+const DEFAULT_RULE: &str = "B3/S23";                    // 起動時のルール（--rule オプションで変更できる）
+const RLE_EXPORT_PATH: &str = "field.rle";              // Wキーでフィールドを書き出すファイル
+const HASHLIFE_STEP_LOG2: u8 = 0;                       // HashLifeで一度に進める世代数の指数（2^k世代）の初期値
+const HASHLIFE_MAX_STEP_LOG2: u8 = 30;                  // HashLifeで一度に進める世代数の指数の上限
+const MIN_CELL_SIZE: f32 = 0.5;                         // ズームアウトしたときのセルの1辺の長さの下限（ポイント）
+const MAX_CELL_SIZE: f32 = 64.0;                        // ズームインしたときのセルの1辺の長さの上限（ポイント）
+const ZOOM_STEP: f32 = 1.1;                             // マウスホイール1段あたりの拡大率
+const PIXELS_PER_SCROLL_LINE: f32 = 40.0;               // タッチパッドのスクロール量を、ホイールの段数に換算するときの1段あたりのピクセル数
+const HUD_FONT_SIZE: u32 = 14;                          // 世代数・個体数の表示の文字の大きさ
+const MIN_ITERATION_INTERVAL_SECONDS: f32 = 1.0 / 64.0; // +キーで短くできる更新間隔の下限（秒）
+const MAX_ITERATION_INTERVAL_SECONDS: f32 = 2.0;        // -キーで長くできる更新間隔の上限（秒）
+const HISTORY_CAPACITY: usize = 1000;                   // 巻き戻せる世代の数
+const TIMELINE_HEIGHT: f32 = 10.0;                      // 画面下部に表示するタイムラインの高さ（ポイント）
+const SEED_ENV_VAR: &str = "GAME_OF_LIFE_SEED";         // 乱数の種を指定する環境変数（--seed オプションが優先される）
+const MANIFEST_EXPORT_PATH: &str = "run.manifest";      // Mキーで再現用のマニフェストを書き出すファイル
+const MAX_DETECTED_PERIOD: usize = 64;                  // フィールドが落ち着いたと判定する周期の上限（世代数）
+const AUTO_RESEED_GRACE_GENERATIONS: u64 = 100;         // 自動で生成し直すまでに、落ち着いてから待つ世代数の初期値
+const STATS_EXPORT_PATH: &str = "stats.csv";            // Xキーで世代ごとの統計を書き出すファイル
+const SPARKLINE_LENGTH: usize = 200;                    // 画面右上の個体数のグラフに表示する世代数
+const SPARKLINE_WIDTH: f32 = 200.0;                     // 個体数のグラフの幅（ポイント）
+const SPARKLINE_HEIGHT: f32 = 48.0;                     // 個体数のグラフの高さ（ポイント）
+const STAMP_PREVIEW_ALPHA: f32 = 0.4;                   // スタンプモードで、置く前のパターンを表示するときの不透明度
+const SESSION_PATH: &str = "game-of-life.session";      // Sキーでセッションを保存し、Lキーで読み込むファイル
+const LENIA_SIZE: usize = 256;                          // Leniaモードのフィールドの1辺のセル数
+const MAX_TEXTURE_SIZE: u32 = 8192;                     // フィールドをテクスチャで描くときの、テクスチャの1辺のテクセル数の上限
+const GRID_MIN_CELL_SIZE: f32 = 4.0;                    // グリッド線を描くセルの1辺の長さの下限（ポイント）
+const FRAME_TIME_SMOOTHING: f32 = 0.05;                 // フレーム時間の移動平均で、新しいフレームの時間に掛ける重み
 
 fn main() {
     nannou::app(model).update(update).run();
 }
 
 struct Model {
-    last_iteration_time: f32, // フィールドを更新した最後の時間（プログラム開始からの経過秒数）
+    last_iteration_time: f32,  // フィールドを更新した最後の時間（プログラム開始からの経過秒数）
     field: Field,
+    backend: Backend,                // 世代を進める方法
+    hashlife_step_log2: u8,          // HashLifeで一度に進める世代数の指数（2^k世代）
+    generation: u64,                 // 起動してから進めた世代数
+    camera: Camera,                  // 画面に映すフィールドの範囲
+    drag: Option<Drag>,              // マウスでドラッグしている操作
+    edit_mode: bool,                 // trueのときは、左ドラッグでセルを描き、右ドラッグで消す
+    paused: bool,                    // trueのときは、時間が経っても世代を進めない
+    iteration_interval: f32,         // フィールドの更新間隔（秒）。+/-キーで変えられる
+    history: History,                // 巻き戻しのために覚えておく、過去のフィールドの状態
+    seed: u64,                       // フィールドを生成した乱数の種
+    alive_ratio: f32,                // フィールド生成時に生存しているセルの割合
+    edited: bool,                    // 生成してから手でセルを編集したかどうか（編集するとマニフェストから再現できない）
+    detector: PeriodDetector,        // フィールドが同じ状態を繰り返し始めたことを検出する
+    settled: Option<(u64, Outcome)>, // フィールドが落ち着いていれば、落ち着いた世代とその状態
+    auto_reseed: Option<u64>,        // Someのときは、落ち着いてからこの世代数が経つと新しい種から生成し直す
+    stats: Stats,                    // 世代ごとの個体数・誕生数・死滅数・生きているセルを囲む長方形
+    stamp: Option<Pattern>,          // Someのときはスタンプモードで、左クリックでこのパターンを置く
+    stamp_index: usize,              // スタンプに使うカタログのパターンの番号
+    render_mode: RenderMode,         // セルの描き方（セルの色・年齢のヒートマップ・残像）
+    cell_size: f32,                  // 起動時のセルの1辺の長さ。カメラを元に戻すときや、読み込んだセッションに合わせてウィンドウの大きさを決めるときに使う
+    window_size: Vec2,               // ウィンドウの大きさ（ポイント）
+    min_field_size: (u32, u32),      // ウィンドウに合わせて縮めるときのフィールドの列数・行数の下限。読み込んだセッションを切り取らないために使う
+    rng: ChaCha8Rng,                 // Rキーや自動で生成し直すときに、新しい乱数の種を選ぶ乱数生成器
+    lenia: Option<Lenia>,            // SomeのときはLeniaモードで、通常のフィールドの代わりにこの連続値のフィールドを進めて描く
+    lenia_preset: usize,             // Leniaモードで選んでいる生き物のプリセットの番号
+    texture: Option<wgpu::Texture>,  // Leniaのフィールドや、画面に映っている範囲のセルを画像として描くためのテクスチャ
+    renderer: Renderer,              // フィールドのセルを描く方法。Dキーで切り替えられる
+    grid_lines: bool,                // trueのときは、セルの境目にグリッド線を描く。Iキーで切り替えられる
+    frame_times: [FrameTime; 2],     // 描く方法ごとの、view関数で1フレームを描くのにかかった時間（ミリ秒）の移動平均
+}
+
+// マウスのドラッグで行っている操作
+enum Drag {
+    Pan(Point2),                              // フィールドを動かす。値は直前のマウスカーソルの位置
+    Paint { state: CellState, from: Point2 }, // 通ったセルをstate（生きた状態や、Wireworldの導体・電子など）にする
+    Scrub,                                    // タイムライン上で、表示する過去の世代を選ぶ
+}
+
+// フィールドの世代を進めるためのバックエンド
+// DenseとSparse以外では、バックエンドが持つ状態で計算し、結果をフィールドに書き戻して描画する
+enum Backend {
+    Dense,               // Field::iterate で1世代ずつ進める
+    BitPacked(BitField), // 1セル1ビットのグリッドで1世代ずつ進める
+    HashLife(HashLife),  // 無限平面上のHashLifeで 2^k 世代ずつ進める
+    Sparse(SparseField), // 端のないフィールドで1世代ずつ進め、フィールドの外のセルも描画する
+}
+
+// フィールドのセルを描く方法
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Renderer {
+    Rects,   // セルごとに長方形（六角形グリッドでは六角形）を描く。セルが多いとフレームの時間の大半を占める
+    Texture, // 画面に映っている範囲を1セル1テクセルの画像にし、1枚のテクスチャとして補間せずに拡大して描く
+}
+
+// Dキーで切り替える順番（frame_timesの並びと同じ）
+const RENDERERS: [Renderer; 2] = [Renderer::Rects, Renderer::Texture];
+
+// 1フレームを描く時間の移動平均（ミリ秒）。まだ測っていなければNone
+// viewはモデルを書き換えられないため、Cellの中に持って書き換える
+type FrameTime = std::cell::Cell<Option<f32>>;
+
+impl std::fmt::Display for Renderer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Renderer::Rects => write!(f, "rects"),
+            Renderer::Texture => write!(f, "texture"),
+        }
+    }
+}
+
+// 画面に映すフィールドの範囲を表現するための構造体
+// セル座標は通常のフィールドと同じく、xは右へ、yは下へ増える
+struct Camera {
+    center: Vec2,   // 画面の中心に映るセル座標（セル(x, y)は x〜x+1, y〜y+1 の範囲を占める）
+    cell_size: f32, // セル一つあたりの1辺の長さ（ポイント）
 }
 
-// フィールドの状態を管理するための構造体
-struct Field {
-    col_n: u32,             // フィールドの列数（横方向のセル数）
-    row_n: u32,             // フィールドの行数（縦方向のセル数）
-    matrix: Vec<Vec<Cell>>, // セルを行列の中に格納する
-}
-
-impl Field {
-    // フィールドを生成するためのメソッド
-    fn new(row_n: u32, col_n: u32, alive_ratio: f32) -> Field {
-       let mut matrix: Vec<Vec<Cell>> = vec![]; 
-       let mut rng = thread_rng();
-       for _x in 0..col_n {
-           let mut col: Vec<Cell> = vec![];
-           for _y in 0..row_n {
-               // alive_ratioの確率で生きているセルを生成する
-               let state = if rng.gen::<f32>() < alive_ratio { CellState::Alive } else { CellState::Dead };
-               // セルの色は生成時に決定。同じ色が次世代でも受け継がれる
-               let cell = Cell::new(state, 0.0, rng.gen_range(0.0..=0.4), rng.gen_range(0.6..=1.0));
-               col.push(cell);
-           }
-           matrix.push(col);
-       }
-       Field{ row_n, col_n, matrix }
-    }
-
-    // 現在のフィールドの状態をもとに次の状態へ移行するためのメソッド
+impl Camera {
+    // セル(x, y)の中心が映る、画面上の位置を返すメソッド
+    fn to_screen(&self, x: f32, y: f32) -> Point2 {
+        vec2(x + 0.5 - self.center.x, self.center.y - y - 0.5) * self.cell_size
+    }
+
+    // 画面上の位置に映っているセル座標を返すメソッド
+    fn to_world(&self, position: Point2) -> Vec2 {
+        self.center + vec2(position.x, -position.y) / self.cell_size
+    }
+
+    // 画面上の位置に映っているセルの座標を返すメソッド
+    // 六角形グリッドでは、奇数行が半セル右にずれて描かれている分を戻してから求める
+    fn cell_at(&self, position: Point2, hexagonal: bool) -> (i64, i64) {
+        let world = self.to_world(position);
+        let y = world.y.floor() as i64;
+        let row_shift = if hexagonal && y.rem_euclid(2) == 1 { 0.5 } else { 0.0 };
+        ((world.x - row_shift).floor() as i64, y)
+    }
+
+    // 画面に映っているセルの範囲 (x0..x1, y0..y1) を返すメソッド
+    // 六角形グリッドで半セルずれる分も含むよう、1セル余分に広げる
+    fn visible(&self, win: Rect) -> (i64, i64, i64, i64) {
+        let top_left = self.to_world(win.top_left());
+        let bottom_right = self.to_world(win.bottom_right());
+        (top_left.x.floor() as i64 - 1, top_left.y.floor() as i64,
+         bottom_right.x.ceil() as i64 + 1, bottom_right.y.ceil() as i64)
+    }
+
+    // 画面上の位置anchorに映るセルを動かさずに、factor倍に拡大するメソッド
+    fn zoom(&mut self, factor: f32, anchor: Point2) {
+        let before = self.to_world(anchor);
+        self.cell_size = (self.cell_size * factor).clamp(MIN_CELL_SIZE, MAX_CELL_SIZE);
+        self.center += before - self.to_world(anchor);
+    }
+}
+
+impl Model {
+    // 選択されているバックエンドでフィールドを次の状態に移行するためのメソッド
+    // 過去に戻った状態から進めると、それより先の履歴は捨てられ、新しい流れに分岐する
     fn iterate(&mut self) {
-        // 次の状態を計算
-        let new_matrix = self.matrix.iter().enumerate().map(|(x, col)| {
-            col.iter().enumerate().map(|(y, cell)| {
-                let neighbor_count = self.alive_neighbour_count_from(x, y);
-                match cell.state {
-                    CellState::Alive => {
-                        match neighbor_count {
-                            0 | 1 => cell.dead_clone(), // 過疎
-                            2 | 3 => *cell, // 生存
-                            _ => cell.dead_clone(), // 過密
-                        }
-                    },
-                    CellState::Dead=> {
-                        match neighbor_count {
-                            3 => cell.alive_clone(), // 誕生
-                            _ => *cell,
-                        }
-                    },
-                }
-            }).collect::<Vec<_>>()
-        }).collect::<Vec<_>>();
-        self.matrix = new_matrix;
+        // Leniaモードでは、Leniaのフィールドを1ステップ進めるだけで、履歴や統計は記録しない
+        if let Some(lenia) = &mut self.lenia {
+            lenia.step();
+            return;
+        }
+        // 描いている途中のセルはまだ計算方法に写していないため、描き終わるまでは世代を進めない
+        if let Some(Drag::Paint { .. }) = self.drag {
+            return;
+        }
+        if self.history.is_rewound() {
+            println!("forked a new run from generation {}", self.generation);
+        }
+        let generations = match &mut self.backend {
+            Backend::Dense => {
+                self.field.iterate();
+                1
+            },
+            Backend::BitPacked(bitfield) => {
+                bitfield.iterate();
+                bitfield.write_to(&mut self.field);
+                1
+            },
+            Backend::HashLife(hashlife) => {
+                hashlife.step(self.hashlife_step_log2);
+                hashlife.write_to(&mut self.field);
+                1 << self.hashlife_step_log2
+            },
+            Backend::Sparse(sparse) => {
+                sparse.iterate();
+                1
+            },
+        };
+        self.generation += generations;
+        self.record_history(generations);
+        self.stats.record(self.generation, self.alive_positions());
+        self.detect_settlement();
     }
 
-    // フィールドの中から特定の場所のセルを取り出すためのメソッド
-    fn get_cell(&self, x: usize, y: usize) -> Cell {
-        self.matrix[x][y]
+    // 生きているセルの座標を返すメソッド（端のないフィールドでは、フィールドの外のセルも含める）
+    fn alive_positions(&self) -> Vec<(i64, i64)> {
+        match &self.backend {
+            Backend::Sparse(sparse) => sparse.cells.iter()
+                .filter(|(_, cell)| cell.state == CellState::Alive)
+                .map(|(&position, _)| position)
+                .collect(),
+            _ => (0..self.field.col_n as usize)
+                .flat_map(|x| (0..self.field.row_n as usize).map(move |y| (x, y)))
+                .filter(|&(x, y)| self.field.rule.counts(self.field.matrix[x][y].state))
+                .map(|(x, y)| (x as i64, y as i64))
+                .collect(),
+        }
     }
 
-    // 与えられた場所に接しているセルのうち生存しているものの数を返すメソッド
-    fn alive_neighbour_count_from(&self, from_x: usize, from_y: usize) -> u32 {
-        let mut alive_count = 0u32;
-        
-        for offset_x in -1..=1 {
-            for offset_y in -1..=1 {
-                // 自分の位置であればスキップ
-                if offset_x == 0 && offset_y == 0 {
-                    continue;
-                }
-                let x = from_x as i32 + offset_x;
-                let y = from_y as i32 + offset_y;
-                // 領域外であればスキップ
-                if x < 0 || (self.col_n as i32) <= x || y < 0 || (self.row_n as i32) <= y {
-                    continue;
-                }
-                // 該当のセルを取得して生存していたらカウントアップ
-                let cell = self.get_cell(x as usize, y as usize);
-                match cell.state {
-                    CellState::Alive => {
-                        alive_count += 1;
-                    },
-                    _ => {},
-                }
+    // フィールドが同じ状態を繰り返し始めたかを調べ、自動での生成し直しが有効なら猶予の後に生成し直すためのメソッド
+    // 1世代ずつフィールドの中で進めるバックエンドでのみ調べる
+    fn detect_settlement(&mut self) {
+        if !matches!(self.backend, Backend::Dense | Backend::BitPacked(_)) {
+            return;
+        }
+        if self.settled.is_none() {
+            if let Some(outcome) = self.detector.observe(&self.field) {
+                println!("settled at generation {}: {}", self.generation, outcome);
+                self.settled = Some((self.generation, outcome));
+            }
+        }
+        if let (Some((settled_generation, _)), Some(grace)) = (self.settled, self.auto_reseed) {
+            if settled_generation + grace <= self.generation {
+                self.reseed();
             }
         }
+    }
 
-        alive_count
+    // 新しい乱数の種からフィールドを生成し直すためのメソッド
+    // 新しい種から生成したフィールドは、世代数0の新しい実行として扱う
+    fn reseed(&mut self) {
+        self.seed = self.rng.gen();
+        self.field.reseed(self.alive_ratio, self.seed);
+        self.generation = 0;
+        self.edited = false;
+        self.history = History::new(&self.field, HISTORY_CAPACITY);
+        self.stats = Stats::new(0, self.alive_positions());
+        print!("{}", self.manifest());
+        // 端のないフィールドもフィールドの範囲内のセルだけから作り直す
+        if let Backend::Sparse(sparse) = &mut self.backend {
+            *sparse = SparseField::from_field(&self.field);
+        }
+        self.sync_backend();
     }
-}
 
-// 個別のセルを表現するための構造体。CloneとCopyを利用できるようにする
-#[derive(Clone, Copy)]
-struct Cell {
-    state: CellState,
-    rgb: [f32; 3],
-}
+    // フィールドの現在の状態を履歴に記録するためのメソッド（generationsは前回の記録から進んだ世代数）
+    // 端のないフィールドはフィールドの外にもセルがあるため、履歴には記録しない
+    fn record_history(&mut self, generations: u64) {
+        if let Backend::Sparse(_) = self.backend {
+            return;
+        }
+        self.history.record(&self.field, generations);
+    }
+
+    // 履歴をたどって、フィールドをcursor番目の状態にするためのメソッド。移動したら一時停止する
+    fn seek_history(&mut self, cursor: usize) {
+        if let Backend::Sparse(_) = self.backend {
+            println!("history is not recorded for the unbounded field");
+            return;
+        }
+        let generations = self.history.seek(&mut self.field, cursor);
+        self.generation = self.generation.saturating_add_signed(generations);
+        self.stats.rewind(self.generation, self.alive_positions());
+        self.paused = true;
+        self.sync_backend();
+    }
 
-impl Cell {
-    fn new(state: CellState, r: f32, g: f32, b: f32) -> Cell {
-        Cell { state, rgb: [r, g, b] }
+    // 生きている（Wireworldでは導体の）セル、または死んだセルを置くときの状態を返すメソッド
+    fn paint_state(&self, alive: bool) -> CellState {
+        match (alive, self.field.rule.family) {
+            (false, _) => CellState::Dead,
+            (true, Family::Wireworld) => CellState::Conductor,
+            (true, Family::LifeLike) => CellState::Alive,
+        }
     }
 
-    // 同じセルの生きているクローンを返すメソッド
-    fn alive_clone(&self) -> Cell {
-        let mut clone = self.clone();
-        clone.state = CellState::Alive;
-        clone
+    // 画面上のpositionから左ボタンで描き始めるときに、描くセルの状態を返すメソッド
+    // Wireworldでは、描き始めたセルの状態を 空→導体→電子の頭→電子の尾→導体 の順に一つ進めた状態で描く
+    // そのため、導体をクリックすると電子の頭を、もう一度クリックすると電子の尾を置ける
+    fn brush(&self, position: Point2) -> CellState {
+        if self.field.rule.family == Family::LifeLike {
+            return CellState::Alive;
+        }
+        let hexagonal = self.field.rule.neighbourhood == Neighbourhood::Hexagonal;
+        let (x, y) = self.camera.cell_at(position, hexagonal);
+        let inside = 0 <= x && x < self.field.col_n as i64 && 0 <= y && y < self.field.row_n as i64;
+        match inside.then(|| self.field.matrix[x as usize][y as usize].state) {
+            Some(CellState::Conductor) => CellState::Head,
+            Some(CellState::Head) => CellState::Tail,
+            _ => CellState::Conductor,
+        }
     }
 
-    // 同じセルの死んでいるクローンを返すメソッド
-    fn dead_clone(&self) -> Cell {
-        let mut clone = self.clone();
-        clone.state = CellState::Dead;
-        clone
+    // (x, y)のセルをstateにするためのメソッド
+    // 端のないフィールド以外では、フィールドの外の座標は無視する
+    fn set_cell(&mut self, (x, y): (i64, i64), state: CellState) {
+        if let Backend::Sparse(sparse) = &mut self.backend {
+            sparse.set((x, y), state == CellState::Alive);
+            return;
+        }
+        if x < 0 || self.field.col_n as i64 <= x || y < 0 || self.field.row_n as i64 <= y {
+            return;
+        }
+        let cell = &mut self.field.matrix[x as usize][y as usize];
+        if cell.state != state {
+            *cell = cell.next_clone(state);
+        }
     }
-}
 
-#[derive(Clone, Copy)]
-enum CellState {
-    Alive, // 生存
-    Dead,  // 死滅
+    // 画面上のfromからtoまでの線分に映っているセルを、すべて描く（または消す）ためのメソッド
+    // カーソルを速く動かしてもセルが飛び飛びにならないよう、半セルずつ区切って調べる
+    fn paint_stroke(&mut self, from: Point2, to: Point2, state: CellState) {
+        let hexagonal = self.field.rule.neighbourhood == Neighbourhood::Hexagonal;
+        let steps = ((to - from).length() / (self.camera.cell_size / 2.0)).ceil().max(1.0) as usize;
+        for i in 0..=steps {
+            let position = from.lerp(to, i as f32 / steps as f32);
+            self.set_cell(self.camera.cell_at(position, hexagonal), state);
+        }
+        self.edited = true;
+    }
+
+    // 画面上のpositionにスタンプのパターンの中心が来るように置いたときの、パターンの左上のセル座標を返すメソッド
+    fn stamp_origin(&self, pattern: &Pattern, position: Point2) -> (i64, i64) {
+        let hexagonal = self.field.rule.neighbourhood == Neighbourhood::Hexagonal;
+        let (x, y) = self.camera.cell_at(position, hexagonal);
+        (x - pattern.width as i64 / 2, y - pattern.height as i64 / 2)
+    }
+
+    // スタンプのパターンを画面上のpositionに置くためのメソッド
+    // パターンを囲む長方形の中のセルは、パターンのとおりに生きた状態か死んだ状態になる
+    fn stamp(&mut self, position: Point2) {
+        let Some(pattern) = self.stamp.take() else {
+            return;
+        };
+        let (origin_x, origin_y) = self.stamp_origin(&pattern, position);
+        let mut alive = vec![vec![false; pattern.height]; pattern.width];
+        for &(x, y) in &pattern.cells {
+            alive[x][y] = true;
+        }
+        for (x, col) in alive.iter().enumerate() {
+            for (y, &is_alive) in col.iter().enumerate() {
+                self.set_cell((origin_x + x as i64, origin_y + y as i64), self.paint_state(is_alive));
+            }
+        }
+        self.stamp = Some(pattern);
+        self.edited = true;
+        self.record_history(0);
+        self.sync_backend();
+    }
+
+    // カタログのstamp_index番目のパターンをスタンプにするためのメソッド
+    fn select_stamp(&mut self) {
+        self.stamp = Pattern::from_catalogue(self.stamp_index);
+        println!("stamp: {}", pattern::CATALOGUE[self.stamp_index].0);
+    }
+
+    // いまのカメラのセルの大きさでウィンドウがちょうど埋まる、フィールドの列数・行数を返すメソッド
+    // それぞれmin_col_n・min_row_nより小さくはしない
+    fn window_field_size(&self, (min_col_n, min_row_n): (u32, u32)) -> (u32, u32) {
+        let col_n = ((self.window_size.x / self.camera.cell_size).ceil() as u32).max(min_col_n);
+        let mut row_n = ((self.window_size.y / self.camera.cell_size).ceil() as u32).max(min_row_n);
+        // 六角形グリッドで境界と行数の偶奇が合わなければ、1行多くする
+        if self.field.boundary.check(self.field.rule.neighbourhood, row_n).is_err() {
+            row_n += 1;
+        }
+        (col_n, row_n)
+    }
+
+    // ウィンドウがちょうど埋まるように、フィールドの大きさをwindow_sizeとカメラのセルの大きさから決め直すためのメソッド
+    // 残るセルはフィールド上の座標を変えずに残す。大きさが変わると履歴は使えなくなるので、記録し直す
+    fn fit_field_to_window(&mut self) {
+        let (col_n, row_n) = self.window_field_size(self.min_field_size);
+        if (col_n, row_n) != (self.field.col_n, self.field.row_n) {
+            self.resize_field(row_n, col_n);
+        }
+    }
+
+    // 六角形グリッドで上下の端をまたぐと行の偶奇が食い違う組み合わせになったら、フィールドを1行増やすメソッド
+    // 境界やルールを切り替えたときに呼ぶ。行を増やすだけなので、いまのセルはそのまま残る
+    fn fit_field_to_boundary(&mut self) {
+        if let Err(err) = self.field.boundary.check(self.field.rule.neighbourhood, self.field.row_n) {
+            println!("{}; adding a row", err);
+            self.resize_field(self.field.row_n + 1, self.field.col_n);
+        }
+    }
+
+    // フィールドの大きさを変え、履歴と計算方法をそれに合わせるメソッド
+    fn resize_field(&mut self, row_n: u32, col_n: u32) {
+        self.field.resize(row_n, col_n);
+        self.edited = true;
+        self.history = History::new(&self.field, HISTORY_CAPACITY);
+        self.sync_backend();
+        println!("field size: {}x{}", col_n, row_n);
+    }
+
+    // ズームアウトしてウィンドウに映るセルの数がフィールドより多くなったら、フィールドを広げてウィンドウを埋めるメソッド
+    // 今あるセルはそのまま残し、ズームでフィールドを縮めることはない
+    // カメラは動かさないので、ズームの基準にした位置はそのまま残る
+    fn grow_field_to_window(&mut self) {
+        let (col_n, row_n) = self.window_field_size((self.field.col_n, self.field.row_n));
+        if (col_n, row_n) != (self.field.col_n, self.field.row_n) {
+            self.resize_field(row_n, col_n);
+        }
+    }
+
+    // フィールド全体がちょうどウィンドウに収まるように映すメソッド
+    fn fit_camera(&mut self) {
+        self.camera = Camera {
+            center: vec2(self.field.col_n as f32, self.field.row_n as f32) / 2.0,
+            cell_size: self.cell_size,
+        };
+    }
+
+    // 実際にセルを描く方法。六角形グリッドの行のずれはテクスチャで表せないため、六角形グリッドでは図形を描く
+    fn renderer(&self) -> Renderer {
+        if self.field.rule.neighbourhood == Neighbourhood::Hexagonal {
+            Renderer::Rects
+        } else {
+            self.renderer
+        }
+    }
+
+    // 描くのに必要なテクスチャの大きさ（テクセル数）。テクスチャを使わなければNoneを返す
+    // フィールドは画面に映っている範囲だけを描くため、フィールドを動かしても変わらないよう、ウィンドウとセルの大きさだけから決める
+    // ホイールで少し拡大・縮小するたびに作り直さないよう、2のべき乗に切り上げる（余った部分には範囲の外のセルを描く）
+    fn texture_size(&self) -> Option<[u32; 2]> {
+        if self.lenia.is_some() {
+            return Some([LENIA_SIZE as u32; 2]);
+        }
+        if self.renderer() != Renderer::Texture {
+            return None;
+        }
+        // Camera::visible の範囲は、端のセルが途中までしか映っていない分と、左右に1セルずつ広げた分だけ大きくなる
+        let cells = self.window_size / self.camera.cell_size;
+        Some([cells.x.ceil() as u32 + 3, cells.y.ceil() as u32 + 1].map(|n| n.next_power_of_two().min(MAX_TEXTURE_SIZE)))
+    }
+
+    // 描く方法ごとの1フレームあたりの時間を "rects 25.3 ms, texture 3.1 ms" の形で返すメソッド
+    fn frame_times(&self) -> String {
+        RENDERERS.iter().zip(&self.frame_times)
+            .filter_map(|(renderer, time)| time.get().map(|time| format!("{} {:.1} ms", renderer, time)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    // startからいままでの時間を、いまの描く方法の1フレームあたりの時間の移動平均に加えるメソッド
+    fn record_frame_time(&self, start: Instant) {
+        let milliseconds = start.elapsed().as_secs_f32() * 1000.0;
+        let average = &self.frame_times[self.renderer() as usize];
+        average.set(Some(average.get().map_or(milliseconds, |average| average + (milliseconds - average) * FRAME_TIME_SMOOTHING)));
+    }
+
+    // Leniaモードで選んでいるプリセットから、Leniaのフィールドを作り直すためのメソッド
+    // スープから始めるプリセットでは、乱数生成器から新しい種を選ぶ
+    fn load_lenia(&mut self) {
+        let preset = &lenia::PRESETS[self.lenia_preset];
+        self.lenia = Some(Lenia::from_preset(LENIA_SIZE, LENIA_SIZE, preset, self.rng.gen()));
+        println!("lenia: {}", preset.name);
+    }
+
+    // 現在の状態を、後で再開するためのセッションとして返すメソッド
+    // 端のないフィールドでは、フィールドの範囲内のセルだけを保存する
+    fn session(&self) -> Session {
+        let mut field = self.field.clone();
+        if let Backend::Sparse(sparse) = &self.backend {
+            sparse.write_to(&mut field);
+        }
+        Session {
+            field,
+            generation: self.generation,
+            seed: self.seed,
+            alive_ratio: self.alive_ratio,
+            edited: self.edited,
+            rng: self.rng.clone(),
+            paused: self.paused,
+            iteration_interval: self.iteration_interval,
+            auto_reseed: self.auto_reseed,
+            render_mode: self.render_mode,
+        }
+    }
+
+    // 保存したセッションの状態から再開するためのメソッド
+    // 履歴と統計はセッションの世代から記録し直し、ウィンドウはフィールドがちょうど収まる大きさにする
+    fn restore(&mut self, app: &App, session: Session) {
+        let threads = self.field.threads;
+        self.field = session.field;
+        self.field.threads = threads;
+        self.generation = session.generation;
+        self.seed = session.seed;
+        self.alive_ratio = session.alive_ratio;
+        self.edited = session.edited;
+        self.rng = session.rng;
+        self.paused = session.paused;
+        self.iteration_interval = session.iteration_interval;
+        self.auto_reseed = session.auto_reseed;
+        self.render_mode = session.render_mode;
+        if let Backend::Sparse(sparse) = &mut self.backend {
+            *sparse = SparseField::from_field(&self.field);
+        }
+        self.sync_backend();
+        self.history = History::new(&self.field, HISTORY_CAPACITY);
+        self.stats = Stats::new(self.generation, self.alive_positions());
+
+        // 先にwindow_sizeを変えておくので、大きさが変わったときのイベントではフィールドは変わらない
+        // OSがウィンドウを画面に収まる大きさに縮めても、読み込んだフィールドは切り取らない
+        self.min_field_size = (self.field.col_n, self.field.row_n);
+        self.window_size = vec2(self.field.col_n as f32, self.field.row_n as f32) * self.cell_size;
+        app.main_window().set_inner_size_points(self.window_size.x, self.window_size.y);
+        self.fit_camera();
+        print!("{}", self.manifest());
+    }
+
+    // 現在の実行を再現するためのマニフェストを返すメソッド
+    fn manifest(&self) -> Manifest {
+        Manifest {
+            seed: self.seed,
+            rule: self.field.rule,
+            boundary: self.field.boundary,
+            col_n: self.field.col_n,
+            row_n: self.field.row_n,
+            alive_ratio: self.alive_ratio,
+            generation: self.generation,
+            backend: match self.backend {
+                Backend::Dense => BackendKind::Dense,
+                Backend::BitPacked(_) => BackendKind::BitPacked,
+                Backend::HashLife(_) => BackendKind::HashLife,
+                Backend::Sparse(_) => BackendKind::Sparse,
+            },
+        }
+    }
+
+    // 生きているセルの数を返すメソッド（HashLifeと端のないフィールドでは、画面外のセルも数える）
+    fn population(&self) -> u64 {
+        match &self.backend {
+            Backend::HashLife(hashlife) => hashlife.population(),
+            Backend::Sparse(sparse) => sparse.population(),
+            _ => self.field.population(),
+        }
+    }
+
+    // フィールドを書き換えたあとに、選択中のバックエンドをフィールドから作り直すためのメソッド
+    // そのバックエンドで扱えないルールや境界になっていれば、通常のフィールドでの計算に戻す
+    // フィールドが書き換えられたので、同じ状態の繰り返しの検出もやり直す
+    fn sync_backend(&mut self) {
+        self.detector.reset();
+        self.settled = None;
+        let supported = match self.backend {
+            Backend::Dense => return,
+            Backend::BitPacked(_) => {
+                BitField::supports(&self.field.rule, self.field.boundary) && self.field.colour_mode == ColourMode::Positional
+            },
+            Backend::HashLife(_) => HashLife::supports(&self.field.rule) && self.field.colour_mode == ColourMode::Positional,
+            Backend::Sparse(_) => SparseField::supports(&self.field.rule),
+        };
+        if !supported {
+            self.backend = Backend::Dense;
+            println!("{} with {} boundary and {} colours is not supported by this backend; switched back to the dense field",
+                self.field.rule, self.field.boundary, self.field.colour_mode);
+            return;
+        }
+        match &mut self.backend {
+            Backend::BitPacked(bitfield) => *bitfield = BitField::from_field(&self.field),
+            Backend::HashLife(hashlife) => *hashlife = HashLife::from_field(&self.field),
+            // 端のないフィールドはフィールドの外にもセルがあるため、作り直さずにルールだけを変える
+            Backend::Sparse(sparse) => {
+                sparse.rule = self.field.rule;
+                sparse.colour_mode = self.field.colour_mode;
+                sparse.trail_generations = render::GHOST_TRAIL_GENERATIONS;
+            },
+            Backend::Dense => {},
+        }
+    }
 }
 
 // モデルなどの初期化を行うための関数
 fn model(app: &App) -> Model {
-    let window_width = COL_N * CELL_SIZE;
-    let window_height = ROW_N * CELL_SIZE;
+    // --manifest オプションでマニフェストが指定されていれば、そこに書かれた世代まで進めたフィールドから始める
+    // 指定がなければ、--seed オプションか環境変数の乱数の種（なければランダムな種）からフィールドを生成する
+    let manifest = match manifest_from_args() {
+        Some(manifest) => manifest,
+        None => {
+            let mut manifest = Manifest {
+                seed: seed_from_args(),
+                rule: rule_from_args(),
+                boundary: Default::default(),
+                col_n: COL_N,
+                row_n: ROW_N,
+                alive_ratio: INITIAL_ALIVE_RATIO,
+                generation: 0,
+                backend: Default::default(),
+            };
+            if let Some(name) = arg_value("--boundary") {
+                match name.parse() {
+                    Ok(boundary) => manifest.boundary = boundary,
+                    Err(err) => eprintln!("{}", err),
+                }
+            }
+            // 六角形グリッドで境界と行数の偶奇が合わなければ、1行多くする
+            if let Err(err) = manifest.check() {
+                eprintln!("{}; adding a row", err);
+                manifest.row_n += 1;
+            }
+            manifest
+        },
+    };
+    let mut field = manifest.replay(colour_mode_from_args());
+    field.threads = threads_from_args();
+    let mut edited = false;
+    if let Some(path) = arg_value("--pattern") {
+        match Pattern::load(&path) {
+            Ok(pattern) => {
+                load_pattern(&mut field, &pattern);
+                edited = true;
+            },
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+    print!("{}", manifest);
+
+    // ウィンドウはフィールド全体がちょうど収まる大きさで開く
+    let cell_size = cell_size_from_args();
+    let window_size = vec2(field.col_n as f32, field.row_n as f32) * cell_size;
     app.new_window()
-       .size(window_width, window_height)
+       .size(window_size.x as u32, window_size.y as u32)
        .view(view)
+       .key_pressed(key_pressed)
+       .mouse_pressed(mouse_pressed)
+       .mouse_released(mouse_released)
+       .mouse_moved(mouse_moved)
+       .mouse_wheel(mouse_wheel)
+       .resized(resized)
        .build()
        .unwrap();
 
-    let model = Model{
+    let history = History::new(&field, HISTORY_CAPACITY);
+    let mut model = Model{
         last_iteration_time: 0.0,
-        field: Field::new(ROW_N, COL_N, INITIAL_ALIVE_RATIO),
+        // 起動時はフィールド全体がちょうどウィンドウに収まるように映す
+        camera: Camera {
+            center: vec2(field.col_n as f32 / 2.0, field.row_n as f32 / 2.0),
+            cell_size,
+        },
+        cell_size,
+        window_size,
+        min_field_size: (1, 1),
+        field,
+        backend: Backend::Dense,
+        hashlife_step_log2: HASHLIFE_STEP_LOG2,
+        generation: manifest.generation,
+        drag: None,
+        edit_mode: false,
+        paused: false,
+        iteration_interval: ITERATION_INTERVAL_SECONDS,
+        history,
+        seed: manifest.seed,
+        alive_ratio: manifest.alive_ratio,
+        edited,
+        detector: PeriodDetector::new(MAX_DETECTED_PERIOD),
+        settled: None,
+        auto_reseed: auto_reseed_from_args(),
+        stats: Stats::new(0, vec![]),
+        stamp: None,
+        stamp_index: 0,
+        render_mode: render_mode_from_args(),
+        // 生成し直したときの種も、最初の種から再現できるようにする
+        rng: ChaCha8Rng::seed_from_u64(manifest.seed),
+        lenia: None,
+        lenia_preset: 0,
+        texture: None,
+        renderer: Renderer::Texture,
+        grid_lines: false,
+        frame_times: Default::default(),
     };
-
+    // マニフェストに書かれた計算方法で続きを進める（HashLifeと端のないフィールドでは、フィールドの外のセルは引き継がれない）
+    model.backend = match manifest.backend {
+        BackendKind::Dense => Backend::Dense,
+        BackendKind::BitPacked => Backend::BitPacked(BitField::from_field(&model.field)),
+        BackendKind::HashLife => Backend::HashLife(HashLife::from_field(&model.field)),
+        BackendKind::Sparse => Backend::Sparse(SparseField::from_field(&model.field)),
+    };
+    model.sync_backend();
+    // パターンに含まれていたルールに切り替わった場合に備えて、行数を確かめ直す
+    model.fit_field_to_boundary();
+    model.stats = Stats::new(model.generation, model.alive_positions());
+    // --session オプションでセッションファイルが指定されていれば、保存した状態から再開する
+    if let Some(path) = arg_value("--session") {
+        match load_session(&path) {
+            Ok(session) => model.restore(app, session),
+            Err(err) => eprintln!("{}", err),
+        }
+    }
     model
 }
 
+// コマンドライン引数の --rule オプションからルールを読み込む関数。指定がなければDEFAULT_RULEを使う
+fn rule_from_args() -> Rule {
+    let rulestring = arg_value("--rule").unwrap_or_else(|| DEFAULT_RULE.to_string());
+    match rulestring.parse() {
+        Ok(rule) => rule,
+        Err(err) => {
+            eprintln!("{}; falling back to {}", err, DEFAULT_RULE);
+            DEFAULT_RULE.parse().unwrap()
+        },
+    }
+}
+
+// コマンドライン引数の --seed オプションか、環境変数SEED_ENV_VARから乱数の種を読み込む関数
+// どちらも指定がなければ、ランダムな種を使う
+fn seed_from_args() -> u64 {
+    let seed = arg_value("--seed").or_else(|| std::env::var(SEED_ENV_VAR).ok());
+    match seed.map(|seed| seed.trim().parse::<u64>()) {
+        Some(Ok(seed)) => seed,
+        Some(Err(err)) => {
+            eprintln!("invalid seed: {}; using a random seed", err);
+            rand::random()
+        },
+        None => rand::random(),
+    }
+}
+
+// コマンドライン引数の --manifest オプションで指定されたファイルからマニフェストを読み込む関数
+fn manifest_from_args() -> Option<Manifest> {
+    let path = arg_value("--manifest")?;
+    let manifest = std::fs::read_to_string(&path)
+        .map_err(|err| format!("failed to read {}: {}", path, err))
+        .and_then(|text| text.parse::<Manifest>());
+    match manifest {
+        Ok(manifest) => Some(manifest),
+        Err(err) => {
+            eprintln!("{}", err);
+            None
+        },
+    }
+}
+
+// コマンドライン引数の --auto-reseed オプションから、落ち着いてから自動で生成し直すまでの世代数を読み込む関数
+// 指定がなければ自動では生成し直さない
+fn auto_reseed_from_args() -> Option<u64> {
+    let grace = arg_value("--auto-reseed")?;
+    match grace.parse() {
+        Ok(grace) => Some(grace),
+        Err(err) => {
+            eprintln!("invalid --auto-reseed {:?}: {}; using {} generations", grace, err, AUTO_RESEED_GRACE_GENERATIONS);
+            Some(AUTO_RESEED_GRACE_GENERATIONS)
+        },
+    }
+}
+
+// コマンドライン引数の --colours オプションから、新しく生まれたセルの色の決め方を読み込む関数
+// 指定がなければ、色はセルの位置で決まる
+fn colour_mode_from_args() -> ColourMode {
+    parse_arg("--colours").unwrap_or_else(|err| {
+        eprintln!("{}; using {} colours", err, ColourMode::default());
+        None
+    }).unwrap_or_default()
+}
+
+// コマンドライン引数の --render オプションから、セルの描き方を読み込む関数
+// 指定がなければ、セルそれぞれの色で描く
+fn render_mode_from_args() -> RenderMode {
+    parse_arg("--render").unwrap_or_else(|err| {
+        eprintln!("{}; using {} rendering", err, RenderMode::default());
+        None
+    }).unwrap_or_default()
+}
+
+// コマンドライン引数の --cell-size オプションから、セルの1辺の長さ（ポイント）を読み込む関数
+// 指定がなければCELL_SIZEを使う
+fn cell_size_from_args() -> f32 {
+    match arg_value("--cell-size").map(|cell_size| cell_size.parse::<f32>()) {
+        Some(Ok(cell_size)) if (MIN_CELL_SIZE..=MAX_CELL_SIZE).contains(&cell_size) => cell_size,
+        Some(_) => {
+            eprintln!("--cell-size expects a number from {} to {}; using {}", MIN_CELL_SIZE, MAX_CELL_SIZE, CELL_SIZE);
+            CELL_SIZE
+        },
+        None => CELL_SIZE,
+    }
+}
+
+// コマンドライン引数の --threads オプションから、次の状態を計算するスレッドの数を読み込む関数
+// 指定がなければ、使えるCPUの数だけのスレッドを使う
+fn threads_from_args() -> usize {
+    let available = std::thread::available_parallelism().map_or(1, |n| n.get());
+    match arg_value("--threads").map(|threads| threads.parse::<usize>()) {
+        Some(Ok(threads)) if threads > 0 => threads,
+        Some(_) => {
+            eprintln!("--threads expects a positive number; using {} threads", available);
+            available
+        },
+        None => available,
+    }
+}
+
+// セッションファイルを読み込む関数
+fn load_session(path: &str) -> Result<Session, String> {
+    std::fs::read_to_string(path)
+        .map_err(|err| format!("failed to read {}: {}", path, err))?
+        .parse::<Session>()
+        .map_err(|err| format!("failed to load {}: {}", path, err))
+}
+
+// 読み込んだパターンだけが生きている状態にフィールドを置き換える関数
+// 配置場所は --at x,y オプションで指定でき、指定がなければ中央に置く
+// パターンにルールが含まれていて --rule の指定がなければ、そのルールを使う
+fn load_pattern(field: &mut Field, pattern: &Pattern) {
+    field.clear();
+    let offset = arg_value("--at").and_then(|at| {
+        let (x, y) = at.split_once(',')?;
+        Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+    });
+    match offset {
+        Some((x, y)) => field.place_pattern(pattern, x, y),
+        None => field.place_pattern_centered(pattern),
+    }
+    if let (Some(rule), None) = (pattern.rule, arg_value("--rule")) {
+        field.rule = rule;
+    }
+}
+
+// キーが押されたときに呼び出される関数
+// 数字キー（1〜9）でルールを、Bキーで境界の種類を切り替え、Wキーで現在のフィールドをRLEとして書き出す
+// Pキーで1セル1ビットのグリッドの、HキーでHashLifeの使用を切り替え、上下キーでHashLifeが一度に進める世代数を倍・半分にする
+// Uキーで端のないフィールドの使用を切り替える（元に戻すと、フィールドの範囲内のセルだけが残る）
+// Eキーで編集モードを、スペースキーで一時停止を切り替え、Nキーで1世代だけ進め、+/-キーで更新間隔を半分・倍にする
+// Cキーですべてのセルを消し、Rキーで新しい乱数の種からセルを生成し直し、Mキーで再現用のマニフェストを書き出す
+// Vキーでセルの描き方（セルの色・年齢のヒートマップ・最近死んだセルの残像）を切り替える
+// Dキーでセルを描く方法（セルごとの図形・1枚のテクスチャ）を切り替え、Iキーでグリッド線を描くかどうかを切り替える
+// [/]キーでセルの1辺の長さを半分・倍にし、ウィンドウがちょうど埋まるようにフィールドの大きさを変える
+// Sキーで現在の状態をセッションとして保存し、Lキーで保存したセッションから再開する
+// Tキーでスタンプモードを切り替え、Tabキーでスタンプにするカタログのパターンを選び、Oキーで時計回りに90°回転、Fキーで左右反転する
+// Xキーで世代ごとの統計をCSVとして書き出し、Gキーで新しく生まれたセルの色の決め方（位置・多数派・平均）を切り替える
+// Aキーで、フィールドが落ち着いてから一定の世代が経ったときに自動で生成し直すかどうかを切り替える
+// 左右キーで履歴を1つ前・後の状態に移動する（最新の状態で右キーを押すと、1世代進める）
+// Kキーで連続値のセル・オートマトン（Lenia）のモードを切り替える。Leniaモードのキーはlenia_key_pressedで扱う
+fn key_pressed(app: &App, model: &mut Model, key: Key) {
+    if model.lenia.is_some() && lenia_key_pressed(model, key) {
+        return;
+    }
+    match key {
+        Key::K => {
+            if model.lenia.take().is_some() {
+                model.fit_camera();
+                println!("lenia: off");
+                return;
+            }
+            model.load_lenia();
+            // Leniaのフィールド全体がちょうどウィンドウに収まるように映す
+            model.camera = Camera {
+                center: vec2(LENIA_SIZE as f32, LENIA_SIZE as f32) / 2.0,
+                cell_size: (model.window_size / LENIA_SIZE as f32).min_element(),
+            };
+        },
+        Key::E => {
+            model.edit_mode = !model.edit_mode;
+            println!("edit mode: {}", if model.edit_mode { "on" } else { "off" });
+        },
+        Key::Space => model.paused = !model.paused,
+        Key::N => model.iterate(),
+        Key::Plus | Key::Equals | Key::NumpadAdd => {
+            model.iteration_interval = (model.iteration_interval / 2.0).max(MIN_ITERATION_INTERVAL_SECONDS);
+        },
+        Key::Minus | Key::NumpadSubtract => {
+            model.iteration_interval = (model.iteration_interval * 2.0).min(MAX_ITERATION_INTERVAL_SECONDS);
+        },
+        Key::C => {
+            model.field.clear();
+            model.edited = true;
+            model.record_history(0);
+            if let Backend::Sparse(sparse) = &mut model.backend {
+                sparse.cells.clear();
+            }
+            model.sync_backend();
+        },
+        Key::R => model.reseed(),
+        Key::A => {
+            model.auto_reseed = match model.auto_reseed {
+                Some(_) => None,
+                None => Some(AUTO_RESEED_GRACE_GENERATIONS),
+            };
+            match model.auto_reseed {
+                Some(grace) => println!("auto reseed: {} generations after settling", grace),
+                None => println!("auto reseed: off"),
+            }
+        },
+        Key::X => {
+            match std::fs::write(STATS_EXPORT_PATH, model.stats.to_csv()) {
+                Ok(()) => println!("saved {} ({} generations)", STATS_EXPORT_PATH, model.stats.records.len()),
+                Err(err) => eprintln!("failed to save {}: {}", STATS_EXPORT_PATH, err),
+            }
+        },
+        Key::M => {
+            if model.edited {
+                eprintln!("warning: the field was edited by hand, so the manifest only reproduces the seeded soup");
+            }
+            match std::fs::write(MANIFEST_EXPORT_PATH, model.manifest().to_string()) {
+                Ok(()) => println!("saved {}", MANIFEST_EXPORT_PATH),
+                Err(err) => eprintln!("failed to save {}: {}", MANIFEST_EXPORT_PATH, err),
+            }
+        },
+        Key::Left => {
+            if model.history.cursor() > 0 {
+                model.seek_history(model.history.cursor() - 1);
+            }
+        },
+        Key::Right => {
+            if model.history.is_rewound() {
+                model.seek_history(model.history.cursor() + 1);
+            } else {
+                model.iterate();
+            }
+        },
+        Key::W => {
+            match std::fs::write(RLE_EXPORT_PATH, model.field.to_pattern().to_rle()) {
+                Ok(()) => println!("saved {}", RLE_EXPORT_PATH),
+                Err(err) => eprintln!("failed to save {}: {}", RLE_EXPORT_PATH, err),
+            }
+        },
+        Key::T => {
+            if model.stamp.take().is_some() {
+                println!("stamp mode: off");
+            } else {
+                model.select_stamp();
+            }
+        },
+        Key::Tab => {
+            model.stamp_index = (model.stamp_index + 1) % pattern::CATALOGUE.len();
+            model.select_stamp();
+        },
+        Key::O => {
+            if let Some(stamp) = &mut model.stamp {
+                *stamp = stamp.rotated();
+            }
+        },
+        Key::F => {
+            if let Some(stamp) = &mut model.stamp {
+                *stamp = stamp.flipped();
+            }
+        },
+        Key::LBracket | Key::RBracket => {
+            // ホイールでのズームと同じくウィンドウの中心を基準にカメラだけを変え、セルは消さない
+            let factor = if key == Key::RBracket { 2.0 } else { 0.5 };
+            model.camera.zoom(factor, pt2(0.0, 0.0));
+            model.grow_field_to_window();
+            println!("cell size: {}", model.camera.cell_size);
+        },
+        Key::S => {
+            match std::fs::write(SESSION_PATH, model.session().to_string()) {
+                Ok(()) => println!("saved {}", SESSION_PATH),
+                Err(err) => eprintln!("failed to save {}: {}", SESSION_PATH, err),
+            }
+        },
+        Key::L => {
+            match load_session(SESSION_PATH) {
+                Ok(session) => {
+                    model.restore(app, session);
+                    println!("loaded {}", SESSION_PATH);
+                },
+                Err(err) => eprintln!("{}", err),
+            }
+        },
+        Key::V => {
+            model.render_mode = model.render_mode.next();
+            println!("render mode: {}", model.render_mode);
+        },
+        Key::D => {
+            model.renderer = RENDERERS[(model.renderer as usize + 1) % RENDERERS.len()];
+            println!("renderer: {} (frame times so far: {})", model.renderer, model.frame_times());
+        },
+        Key::I => {
+            model.grid_lines = !model.grid_lines;
+            println!("grid lines: {}", if model.grid_lines { "on" } else { "off" });
+        },
+        Key::G => {
+            model.field.colour_mode = model.field.colour_mode.next();
+            // 色を受け継ぐモードでは、セルをパレットの色で塗り直す
+            model.field.recolour(model.seed);
+            if let Backend::Sparse(sparse) = &mut model.backend {
+                model.field.colour_mode.repaint(sparse.cells.values_mut(), model.seed);
+            }
+            model.sync_backend();
+            println!("colours: {}", model.field.colour_mode);
+        },
+        Key::B => {
+            model.field.boundary = model.field.boundary.next();
+            model.fit_field_to_boundary();
+            model.sync_backend();
+            println!("boundary: {}", model.field.boundary);
+        },
+        Key::P => {
+            if let Backend::BitPacked(_) = model.backend {
+                model.backend = Backend::Dense;
+                println!("backend: dense field");
+            } else if model.field.colour_mode != ColourMode::Positional {
+                println!("the bit-packed grid does not support {} colours", model.field.colour_mode);
+            } else if BitField::supports(&model.field.rule, model.field.boundary) {
+                model.backend = Backend::BitPacked(BitField::from_field(&model.field));
+                println!("backend: bit-packed grid");
+            } else {
+                println!("the bit-packed grid does not support {} with {} boundary", model.field.rule, model.field.boundary);
+            }
+        },
+        Key::H => {
+            if let Backend::HashLife(hashlife) = &model.backend {
+                println!("backend: dense field (HashLife reached generation {} with population {})",
+                    hashlife.generation, hashlife.population());
+                model.backend = Backend::Dense;
+            } else if model.field.colour_mode != ColourMode::Positional {
+                println!("HashLife does not support {} colours", model.field.colour_mode);
+            } else if HashLife::supports(&model.field.rule) {
+                // HashLifeは無限平面上で計算するため、境界の設定は使われない
+                model.backend = Backend::HashLife(HashLife::from_field(&model.field));
+                println!("backend: HashLife (2^{} generations per step)", model.hashlife_step_log2);
+            } else {
+                println!("HashLife does not support {}", model.field.rule);
+            }
+        },
+        Key::U => {
+            if let Backend::Sparse(sparse) = &model.backend {
+                sparse.write_to(&mut model.field);
+                model.backend = Backend::Dense;
+                model.record_history(0);
+                println!("backend: dense field");
+            } else if SparseField::supports(&model.field.rule) {
+                model.backend = Backend::Sparse(SparseField::from_field(&model.field));
+                model.sync_backend();
+                println!("backend: unbounded sparse field");
+            } else {
+                println!("the unbounded field does not support {}", model.field.rule);
+            }
+        },
+        Key::Up | Key::Down => {
+            model.hashlife_step_log2 = if key == Key::Up {
+                (model.hashlife_step_log2 + 1).min(HASHLIFE_MAX_STEP_LOG2)
+            } else {
+                model.hashlife_step_log2.saturating_sub(1)
+            };
+            println!("HashLife step: 2^{} generations", model.hashlife_step_log2);
+        },
+        _ => {
+            if let Some(preset_index) = preset_index(key, rule::PRESETS.len()) {
+                let rule = Rule::preset(preset_index).unwrap();
+                model.field.rule = rule;
+                model.fit_field_to_boundary();
+                model.sync_backend();
+                println!("rule: {} ({})", rule::PRESETS[preset_index].0, rule);
+            }
+        },
+    }
+}
+
+// Leniaモードでキーが押されたときに呼び出される関数。扱ったキーならtrueを返す
+// 数字キーで生き物のプリセットを選び、Rキーで選んでいるプリセットを置き直し、Cキーですべてのセルを0にする
+// それ以外のキー（一時停止・1ステップ進める・更新間隔など）は通常のモードと同じように扱う
+fn lenia_key_pressed(model: &mut Model, key: Key) -> bool {
+    match key {
+        Key::R => model.load_lenia(),
+        Key::C => {
+            if let Some(lenia) = &mut model.lenia {
+                lenia.cells.fill(0.0);
+            }
+        },
+        _ => match preset_index(key, lenia::PRESETS.len()) {
+            Some(preset_index) => {
+                model.lenia_preset = preset_index;
+                model.load_lenia();
+            },
+            None => return false,
+        },
+    }
+    true
+}
+
+// 数字キーに対応するプリセット番号を返す関数（count個のプリセットがあるとき）
+fn preset_index(key: Key, count: usize) -> Option<usize> {
+    let keys = [Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9];
+    keys.iter().position(|&k| k == key).filter(|&i| i < count)
+}
+
+// マウスのボタンが押されたときに呼び出される関数。ドラッグを始める
+// 編集モードでは左ボタンでセル（Wireworldでは導体。導体の上から描くと電子の頭、頭の上からは電子の尾）を描き、右ボタンでセルを消す。それ以外では左ボタン（と常に中ボタン）でフィールドを動かす
+// タイムラインの上で左ボタンを押したときは、ドラッグしている間、過去の世代を選ぶ
+// スタンプモードでは、左ボタンでカーソルの位置にパターンを置く
+// Leniaモードでは、フィールドを動かすことだけができる
+fn mouse_pressed(app: &App, model: &mut Model, button: MouseButton) {
+    let position = app.mouse.position();
+    if model.lenia.is_some() {
+        if let MouseButton::Left | MouseButton::Middle = button {
+            model.drag = Some(Drag::Pan(position));
+        }
+        return;
+    }
+    if button == MouseButton::Left && !model.history.is_empty() && timeline_rect(app.window_rect()).contains(position) {
+        model.drag = Some(Drag::Scrub);
+        scrub_timeline(app, model, position);
+        return;
+    }
+    if button == MouseButton::Left && model.stamp.is_some() {
+        model.stamp(position);
+        return;
+    }
+    model.drag = match button {
+        MouseButton::Left if model.edit_mode => Some(Drag::Paint { state: model.brush(position), from: position }),
+        MouseButton::Right if model.edit_mode => Some(Drag::Paint { state: CellState::Dead, from: position }),
+        MouseButton::Left | MouseButton::Middle => Some(Drag::Pan(position)),
+        _ => return,
+    };
+    if let Some(Drag::Paint { state, .. }) = model.drag {
+        model.paint_stroke(position, position, state);
+    }
+}
+
+// マウスのボタンが離されたときに呼び出される関数。ドラッグを終える
+// セルを描き終えたら、編集した状態として履歴に記録する
+fn mouse_released(_app: &App, model: &mut Model, _button: MouseButton) {
+    // 描いたセルは、描き終わったときにまとめて計算方法に写す
+    if let Some(Drag::Paint { .. }) = model.drag.take() {
+        model.sync_backend();
+        model.record_history(0);
+    }
+}
+
+// マウスカーソルが動いたときに呼び出される関数
+// ドラッグ中であれば、カーソルに合わせてフィールドを動かすか、通った場所のセルを描く（消す）
+fn mouse_moved(app: &App, model: &mut Model, position: Point2) {
+    match model.drag {
+        Some(Drag::Pan(from)) => {
+            let delta = position - from;
+            model.camera.center -= vec2(delta.x, -delta.y) / model.camera.cell_size;
+            model.drag = Some(Drag::Pan(position));
+        },
+        Some(Drag::Paint { state, from }) => {
+            model.paint_stroke(from, position, state);
+            model.drag = Some(Drag::Paint { state, from: position });
+        },
+        Some(Drag::Scrub) => scrub_timeline(app, model, position),
+        None => {},
+    }
+}
+
+// タイムラインを表示する、画面下部のRectを返す関数
+fn timeline_rect(win: Rect) -> Rect {
+    let padding = HUD_FONT_SIZE as f32 / 2.0;
+    Rect::from_w_h(win.w() - 2.0 * padding, TIMELINE_HEIGHT).mid_bottom_of(win.pad(padding))
+}
+
+// タイムライン上の位置に対応する、履歴の中の状態を表示する関数
+fn scrub_timeline(app: &App, model: &mut Model, position: Point2) {
+    let timeline = timeline_rect(app.window_rect());
+    let ratio = ((position.x - timeline.left()) / timeline.w()).clamp(0.0, 1.0);
+    model.seek_history((ratio * model.history.len() as f32).round() as usize);
+}
+
+// マウスホイールが回されたときに呼び出される関数。カーソルの位置を中心に拡大・縮小する
+fn mouse_wheel(app: &App, model: &mut Model, delta: MouseScrollDelta, _phase: TouchPhase) {
+    let lines = match delta {
+        MouseScrollDelta::LineDelta(_, y) => y,
+        MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_SCROLL_LINE,
+    };
+    model.camera.zoom(ZOOM_STEP.powf(lines), app.mouse.position());
+}
+
+// ウィンドウの大きさが変わったときに呼び出される関数
+// ウィンドウの左上に映っているセルが動かないようにカメラをずらし、ウィンドウが埋まるようにフィールドの大きさを変える
+fn resized(_app: &App, model: &mut Model, size: Vec2) {
+    let delta = size - model.window_size;
+    model.camera.center += delta / 2.0 / model.camera.cell_size;
+    model.window_size = size;
+    model.fit_field_to_window();
+}
+
 // 秒間60回のupdateイベントごとに呼び出される関数。この中でモデルをアップデートする
 fn update(app: &App, model: &mut Model, _update: Update) {
-    // 最後のフィールド更新時間から既定のインターバルが経過していれば次の状態に移行する
-    if (app.time - model.last_iteration_time) >= ITERATION_INTERVAL_SECONDS {
-        model.field.iterate();
+    // 必要なテクスチャの大きさが変わったときだけ、テクスチャを作り直す（使わなくなったら捨てる）
+    let size = model.texture_size();
+    if model.texture.as_ref().map(|texture| texture.size()) != size {
+        model.texture = size.map(|size| {
+            wgpu::TextureBuilder::new()
+                .size(size)
+                .format(wgpu::TextureFormat::Rgba8UnormSrgb)
+                .usage(wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING)
+                .build(app.main_window().device())
+        });
+    }
+
+    // 一時停止中でなく、最後のフィールド更新時間から更新間隔が経過していれば次の状態に移行する
+    if !model.paused && (app.time - model.last_iteration_time) >= model.iteration_interval {
+        model.iterate();
         model.last_iteration_time = app.time; // 最後のイテレーションの時間をモデルの中に保持
     }
 }
 
 // モデルの内容をもとにフレームを描画するための関数
 fn view(app: &App, model: &Model, frame: Frame) {
+    // 描く方法ごとの速さを比べるため、この関数で描き終えるまでの時間を測る（垂直同期の待ち時間や、世代を進める時間は含まない）
+    let start = Instant::now();
     let draw = app.draw();
-    draw.background().rgb(0.11, 0.12, 0.13);
-
-    // 画面左上を基準に描画していくため、左上のRectを取得
+    let [r, g, b] = render::BACKGROUND;
+    draw.background().rgb(r, g, b);
     let win = app.window_rect();
-    let top_left_rect = Rect::from_w_h(CELL_SIZE as f32, CELL_SIZE as f32).top_left_of(win);
-    
-    // セル一つ一つを描画する
-    for (x, col) in model.field.matrix.iter().enumerate() {
-        for (y, cell) in col.iter().enumerate() {
-            match cell.state {
-                CellState::Dead => {},
-                CellState::Alive => {
-                    let shift_x = x as f32 * CELL_SIZE as f32;
-                    let shift_y = -1.0 * y as f32 * CELL_SIZE as f32;
-                    let current_rect = top_left_rect.shift_x(shift_x).shift_y(shift_y);
-                    let [r, g, b] = cell.rgb;
-                    draw.rect()
-                        .xy(current_rect.xy())
-                        .wh(current_rect.wh())
-                        .rgb(r, g, b);
+
+    // Leniaモードでは、セルの値を色に変えた画像をテクスチャに書き込み、1枚の画像としてカメラに合わせて描く
+    if let (Some(lenia), Some(texture)) = (&model.lenia, &model.texture) {
+        let window = app.main_window();
+        texture.upload_data(window.device(), &mut frame.command_encoder(), &lenia.to_rgba());
+        draw_texture(&draw, model, texture, (0, 0));
+
+        let mut hud = format!("lenia: {}\nstep {}\nmass {:.1}\ninterval {:.3}s",
+            lenia::PRESETS[model.lenia_preset].name, lenia.steps, lenia.mass(), model.iteration_interval);
+        if model.paused {
+            hud += "\npaused";
+        }
+        draw_hud(&draw, win, &hud);
+        draw.to_frame(app, &frame).unwrap();
+        return;
+    }
+
+    // 画面に映っている範囲のセルだけを描画する
+    // テクスチャで描くときは、範囲のセルを1セル1テクセルの画像にしてから、1枚の画像として描く
+    let (x0, y0, x1, y1) = model.camera.visible(win);
+    let (col_n, row_n) = (model.field.col_n as i64, model.field.row_n as i64);
+    match (&model.backend, &model.texture) {
+        (_, Some(texture)) if model.renderer() == Renderer::Texture => {
+            let [width, height] = texture.size();
+            let (rule, mode) = (&model.field.rule, model.render_mode);
+            let texels = match &model.backend {
+                Backend::Sparse(sparse) => {
+                    render::texels(sparse.cells.iter().map(|(&position, cell)| (position, cell)), rule, mode, (x0, y0), width, height)
+                },
+                _ => {
+                    let columns = x0.max(0)..(x0 + width as i64).min(col_n);
+                    let cells = columns.flat_map(|x| {
+                        (y0.max(0)..(y0 + height as i64).min(row_n)).map(move |y| ((x, y), &model.field.matrix[x as usize][y as usize]))
+                    });
+                    render::texels(cells, rule, mode, (x0, y0), width, height)
                 },
+            };
+            let window = app.main_window();
+            texture.upload_data(window.device(), &mut frame.command_encoder(), &texels);
+            draw_texture(&draw, model, texture, (x0, y0));
+        },
+        (Backend::Sparse(sparse), _) => {
+            for (&(x, y), cell) in &sparse.cells {
+                if x0 <= x && x < x1 && y0 <= y && y < y1 {
+                    draw_cell(&draw, model, x, y, cell);
+                }
+            }
+        },
+        _ => {
+            for x in x0.max(0)..x1.min(col_n) {
+                for y in y0.max(0)..y1.min(row_n) {
+                    draw_cell(&draw, model, x, y, &model.field.matrix[x as usize][y as usize]);
+                }
             }
+        },
+    }
+
+    // セルの境目にグリッド線を描く。端のあるフィールドでは、フィールドの中だけに描く
+    if model.grid_lines && GRID_MIN_CELL_SIZE <= model.camera.cell_size && model.field.rule.neighbourhood != Neighbourhood::Hexagonal {
+        let (x0, y0, x1, y1) = match model.backend {
+            Backend::Sparse(_) => (x0, y0, x1, y1),
+            _ => (x0.max(0), y0.max(0), x1.min(col_n), y1.min(row_n)),
+        };
+        // セル(x, y)の左上の角が映る、画面上の位置
+        let corner = |x: i64, y: i64| model.camera.to_screen(x as f32 - 0.5, y as f32 - 0.5);
+        for x in x0..=x1 {
+            draw.line().start(corner(x, y0)).end(corner(x, y1)).weight(1.0).rgba(1.0, 1.0, 1.0, 0.1);
+        }
+        for y in y0..=y1 {
+            draw.line().start(corner(x0, y)).end(corner(x1, y)).weight(1.0).rgba(1.0, 1.0, 1.0, 0.1);
         }
     }
 
+    // スタンプモードでは、置く前のパターンをカーソルの位置に半透明で表示する
+    if let Some(pattern) = &model.stamp {
+        let (origin_x, origin_y) = model.stamp_origin(pattern, app.mouse.position());
+        for &(x, y) in &pattern.cells {
+            draw_shape(&draw, model, origin_x + x as i64, origin_y + y as i64, [1.0, 1.0, 1.0, STAMP_PREVIEW_ALPHA]);
+        }
+    }
+
+    // 画面左上に世代数・個体数と、更新間隔などの状態を表示する
+    let mut hud = format!("generation {}\npopulation {}\ninterval {:.3}s\nseed {}",
+        model.generation, model.population(), model.iteration_interval, model.seed);
+    if let Some((generation, outcome)) = model.settled {
+        hud += &format!("\n{} since generation {}", outcome, generation);
+    }
+    if model.paused {
+        hud += "\npaused";
+    }
+    if model.edit_mode {
+        hud += "\nedit mode";
+    }
+    if model.stamp.is_some() {
+        hud += &format!("\nstamp: {}", pattern::CATALOGUE[model.stamp_index].0);
+    }
+    hud += &format!("\nrenderer {} ({})", model.renderer(), model.frame_times());
+    draw_hud(&draw, win, &hud);
+
+    // 画面右上に、直近の世代の個体数の推移をグラフで表示する
+    let sparkline = Rect::from_w_h(SPARKLINE_WIDTH, SPARKLINE_HEIGHT).top_right_of(win.pad(HUD_FONT_SIZE as f32 / 2.0));
+    let populations: Vec<u64> = model.stats.recent_populations(SPARKLINE_LENGTH).collect();
+    let max_population = populations.iter().copied().max().unwrap_or(0).max(1);
+    draw.rect()
+        .xy(sparkline.xy())
+        .wh(sparkline.wh())
+        .rgba(0.0, 0.0, 0.0, 0.4);
+    if populations.len() >= 2 {
+        let points = populations.iter().enumerate().map(|(i, &population)| {
+            let x = sparkline.left() + sparkline.w() * i as f32 / (SPARKLINE_LENGTH - 1) as f32;
+            let y = sparkline.bottom() + sparkline.h() * population as f32 / max_population as f32;
+            vec2(x, y)
+        });
+        draw.polyline()
+            .weight(1.5)
+            .points(points)
+            .rgb(0.4, 0.9, 0.6);
+    }
+    draw.text(&max_population.to_string())
+        .xy(sparkline.xy())
+        .wh(sparkline.pad(2.0).wh())
+        .font_size(HUD_FONT_SIZE - 4)
+        .right_justify()
+        .align_text_top()
+        .rgb(0.9, 0.9, 0.9);
+
+    // 画面下部に、覚えている履歴の長さと、いま表示している位置をタイムラインとして表示する
+    if !model.history.is_empty() {
+        let timeline = timeline_rect(win);
+        let ratio = model.history.cursor() as f32 / model.history.len() as f32;
+        draw.rect()
+            .xy(timeline.xy())
+            .wh(timeline.wh())
+            .rgba(0.9, 0.9, 0.9, 0.2);
+        draw.rect()
+            .x_y(timeline.left() + timeline.w() * ratio / 2.0, timeline.y())
+            .w_h(timeline.w() * ratio, timeline.h())
+            .rgba(0.9, 0.9, 0.9, 0.5);
+        draw.rect()
+            .x_y(timeline.left() + timeline.w() * ratio, timeline.y())
+            .w_h(TIMELINE_HEIGHT / 2.0, TIMELINE_HEIGHT * 1.6)
+            .rgb(0.9, 0.9, 0.9);
+    }
+
     draw.to_frame(app, &frame).unwrap();
+    model.record_frame_time(start);
+}
+
+// 画面左上に、状態を表す文字列hudを表示する関数
+fn draw_hud(draw: &Draw, win: Rect, hud: &str) {
+    let hud_rect = Rect::from_w_h(win.w() / 2.0, (HUD_FONT_SIZE * 8) as f32).top_left_of(win.pad(HUD_FONT_SIZE as f32 / 2.0));
+    draw.text(hud)
+        .xy(hud_rect.xy())
+        .wh(hud_rect.wh())
+        .font_size(HUD_FONT_SIZE)
+        .left_justify()
+        .align_text_top()
+        .rgb(0.9, 0.9, 0.9);
+}
+
+// セル(x, y)を、カメラに合わせた位置と大きさで描画する関数
+// 色は描き方によって変わり、何も描かないセルもある
+fn draw_cell(draw: &Draw, model: &Model, x: i64, y: i64, cell: &Cell) {
+    if let Some(rgba) = model.render_mode.colour(cell, &model.field.rule) {
+        draw_shape(draw, model, x, y, rgba);
+    }
+}
+
+// テクスチャを、左上のテクセルがセル(x0, y0)に重なり、1テクセルが1セルになるようにカメラに合わせて描く関数
+// 拡大したときにセルの境目がぼやけないよう、補間しないサンプラーを使う
+fn draw_texture(draw: &Draw, model: &Model, texture: &wgpu::Texture, (x0, y0): (i64, i64)) {
+    let [width, height] = texture.size();
+    let size = vec2(width as f32, height as f32);
+    let sampler = wgpu::SamplerBuilder::new()
+        .mag_filter(wgpu::FilterMode::Nearest)
+        .min_filter(wgpu::FilterMode::Nearest)
+        .into_descriptor();
+    draw.sampler(sampler)
+        .texture(texture)
+        .xy(model.camera.to_screen(x0 as f32 + size.x / 2.0 - 0.5, y0 as f32 + size.y / 2.0 - 0.5))
+        .wh(size * model.camera.cell_size);
+}
+
+// セル(x, y)の場所に、グリッドに合わせた形（正方形または六角形）をrgbaの色で描く関数
+fn draw_shape(draw: &Draw, model: &Model, x: i64, y: i64, [r, g, b, a]: [f32; 4]) {
+    let cell_size = model.camera.cell_size;
+    let center = model.camera.to_screen(x as f32, y as f32);
+    if model.field.rule.neighbourhood == Neighbourhood::Hexagonal {
+        // 六角形グリッドでは奇数行を半セル右にずらし、上下の行と1/4ずつ重なる六角形を描く
+        let row_shift = if y.rem_euclid(2) == 0 { 0.0 } else { cell_size / 2.0 };
+        let center = center + vec2(row_shift, 0.0);
+        let (hw, hh) = (cell_size / 2.0, cell_size * 2.0 / 3.0);
+        let points = [(0.0, hh), (hw, hh / 2.0), (hw, -hh / 2.0), (0.0, -hh), (-hw, -hh / 2.0), (-hw, hh / 2.0)]
+            .map(|(px, py)| center + vec2(px, py));
+        draw.polygon()
+            .points(points)
+            .rgba(r, g, b, a);
+    } else {
+        draw.rect()
+            .xy(center)
+            .w_h(cell_size, cell_size)
+            .rgba(r, g, b, a);
+    }
 }
@@ -0,0 +1,279 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::bitfield::BitField;
+use crate::boundary::Boundary;
+use crate::colour::ColourMode;
+use crate::hashlife::HashLife;
+use crate::rule::Rule;
+use crate::sparse::SparseField;
+use crate::Field;
+
+// フィールドの世代を進めた計算方法
+// HashLifeと端のないフィールドは無限の平面で計算するため、同じ種でも端のあるフィールドとは違う結果になる
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BackendKind {
+    #[default]
+    Dense,     // Field::iterate で1世代ずつ進める
+    BitPacked, // 1セル1ビットのグリッドで1世代ずつ進める（結果は密なフィールドと同じ）
+    HashLife,  // 無限平面上のHashLifeで進める
+    Sparse,    // 端のないフィールドで1世代ずつ進める
+}
+
+pub const BACKENDS: [BackendKind; 4] = [BackendKind::Dense, BackendKind::BitPacked, BackendKind::HashLife, BackendKind::Sparse];
+
+impl BackendKind {
+    // ルールと境界の組み合わせをこの計算方法で扱えるか確かめるメソッド
+    pub fn check(&self, rule: &Rule, boundary: Boundary) -> Result<(), String> {
+        let supported = match self {
+            BackendKind::Dense => true,
+            BackendKind::BitPacked => BitField::supports(rule, boundary),
+            BackendKind::HashLife => HashLife::supports(rule),
+            BackendKind::Sparse => SparseField::supports(rule),
+        };
+        if supported {
+            Ok(())
+        } else {
+            Err(format!("the {} backend does not support {} with {} boundary", self, rule, boundary))
+        }
+    }
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            BackendKind::Dense => "dense",
+            BackendKind::BitPacked => "bit-packed",
+            BackendKind::HashLife => "hashlife",
+            BackendKind::Sparse => "sparse",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BackendKind, String> {
+        BACKENDS.iter()
+            .find(|backend| backend.to_string().eq_ignore_ascii_case(s.trim()))
+            .copied()
+            .ok_or_else(|| format!("unknown backend {:?}: expected one of dense, bit-packed, hashlife, sparse", s))
+    }
+}
+
+// 実行を再現するための情報をまとめた構造体
+// 同じ内容のマニフェストからは、同じ初期状態のフィールドを作り、同じ世代まで進めることができる
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Manifest {
+    pub seed: u64,            // フィールドを生成した乱数の種
+    pub rule: Rule,           // 誕生・生存の条件となるルール
+    pub boundary: Boundary,   // フィールドの端の扱い
+    pub col_n: u32,           // フィールドの列数
+    pub row_n: u32,           // フィールドの行数
+    pub alive_ratio: f32,     // フィールド生成時に生存しているセルの割合
+    pub generation: u64,      // 生成してから進めた世代数
+    pub backend: BackendKind, // 世代を進めた計算方法
+}
+
+impl Manifest {
+    // マニフェストに書かれた初期状態のフィールドを、色の決め方colour_modeで塗って作るためのメソッド
+    pub fn initial_field(&self, colour_mode: ColourMode) -> Field {
+        let mut field = Field::with_seed(self.row_n, self.col_n, self.alive_ratio, self.seed);
+        field.rule = self.rule;
+        field.boundary = self.boundary;
+        field.colour_mode = colour_mode;
+        field.recolour(self.seed);
+        field
+    }
+
+    // ルール・境界・大きさの組み合わせでフィールドを作れるか確かめるメソッド
+    pub fn check(&self) -> Result<(), String> {
+        self.boundary.check(self.rule.neighbourhood, self.row_n)?;
+        self.backend.check(&self.rule, self.boundary)
+    }
+
+    // 初期状態のフィールドを作り、マニフェストに書かれた計算方法で書かれた世代まで進めるためのメソッド
+    // HashLifeと端のないフィールドでは、フィールドの範囲に入っているセルだけを返す
+    // 色を受け継ぐモードでは、初めからcolour_modeで進めたときと同じ色になるよう、進める前に塗る
+    pub fn replay(&self, colour_mode: ColourMode) -> Field {
+        let mut field = self.initial_field(colour_mode);
+        match self.backend {
+            BackendKind::Dense => {
+                for _ in 0..self.generation {
+                    field.iterate();
+                }
+            },
+            BackendKind::BitPacked => {
+                let mut bitfield = BitField::from_field(&field);
+                for _ in 0..self.generation {
+                    bitfield.iterate();
+                }
+                bitfield.write_to(&mut field);
+            },
+            BackendKind::HashLife => {
+                // 世代数を2進数で表したときに1になっている桁ごとに、2^j 世代ずつ進める
+                let mut hashlife = HashLife::from_field(&field);
+                for j in 0..u64::BITS as u8 {
+                    if self.generation >> j & 1 == 1 {
+                        hashlife.step(j);
+                    }
+                }
+                hashlife.write_to(&mut field);
+            },
+            BackendKind::Sparse => {
+                let mut sparse = SparseField::from_field(&field);
+                for _ in 0..self.generation {
+                    sparse.iterate();
+                }
+                sparse.write_to(&mut field);
+            },
+        }
+        field
+    }
+}
+
+// "key = value" を1行に1つずつ並べた形式で書き出す
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "seed = {}", self.seed)?;
+        writeln!(f, "rule = {}", self.rule)?;
+        writeln!(f, "boundary = {}", self.boundary)?;
+        writeln!(f, "size = {}x{}", self.col_n, self.row_n)?;
+        writeln!(f, "alive_ratio = {}", self.alive_ratio)?;
+        writeln!(f, "generation = {}", self.generation)?;
+        writeln!(f, "backend = {}", self.backend)
+    }
+}
+
+// Displayで書き出した形式を読み込む。#で始まる行と空行は無視する
+impl FromStr for Manifest {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Manifest, String> {
+        let (mut seed, mut rule, mut boundary, mut size, mut alive_ratio, mut generation, mut backend) =
+            (None, None, None, None, None, None, None);
+        for line in s.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let (key, value) = line.split_once('=')
+                .ok_or_else(|| format!("invalid manifest line {:?}", line))?;
+            let value = value.trim();
+            let invalid = |err: &dyn fmt::Display| format!("invalid manifest {} {:?}: {}", key.trim(), value, err);
+            match key.trim() {
+                "seed" => seed = Some(value.parse::<u64>().map_err(|err| invalid(&err))?),
+                "rule" => rule = Some(value.parse::<Rule>()?),
+                "boundary" => boundary = Some(value.parse::<Boundary>()?),
+                "size" => {
+                    let (col_n, row_n) = value.split_once('x').ok_or_else(|| invalid(&"expected <columns>x<rows>"))?;
+                    size = Some((
+                        col_n.trim().parse::<u32>().map_err(|err| invalid(&err))?,
+                        row_n.trim().parse::<u32>().map_err(|err| invalid(&err))?,
+                    ));
+                },
+                "alive_ratio" => alive_ratio = Some(value.parse::<f32>().map_err(|err| invalid(&err))?),
+                "generation" => generation = Some(value.parse::<u64>().map_err(|err| invalid(&err))?),
+                "backend" => backend = Some(value.parse::<BackendKind>()?),
+                key => return Err(format!("unknown manifest key {:?}", key)),
+            }
+        }
+        let (col_n, row_n) = size.ok_or("manifest is missing size")?;
+        let manifest = Manifest {
+            seed: seed.ok_or("manifest is missing seed")?,
+            rule: rule.ok_or("manifest is missing rule")?,
+            boundary: boundary.unwrap_or_default(),
+            col_n,
+            row_n,
+            alive_ratio: alive_ratio.ok_or("manifest is missing alive_ratio")?,
+            generation: generation.unwrap_or(0),
+            backend: backend.unwrap_or_default(),
+        };
+        manifest.check()?;
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CellState;
+
+    fn states(field: &Field) -> Vec<CellState> {
+        field.matrix.iter().flatten().map(|cell| cell.state).collect()
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let manifest = Manifest {
+            seed: 1234567890123,
+            rule: "B36/S23".parse().unwrap(),
+            boundary: Boundary::Torus,
+            col_n: 128,
+            row_n: 72,
+            alive_ratio: 0.25,
+            generation: 345,
+            backend: BackendKind::BitPacked,
+        };
+        let text = manifest.to_string();
+        assert_eq!(text.parse::<Manifest>().unwrap(), manifest);
+        assert!(text.contains("size = 128x72\n"));
+    }
+
+    #[test]
+    fn rejects_incomplete_manifests() {
+        assert!("seed = 1\nrule = B3/S23\nalive_ratio = 0.5".parse::<Manifest>().is_err());
+        assert!("seed = 1\nrule = B3/S23\nsize = 10\nalive_ratio = 0.5".parse::<Manifest>().is_err());
+        assert!("seed = x\nrule = B3/S23\nsize = 10x10\nalive_ratio = 0.5".parse::<Manifest>().is_err());
+        assert!("seed = 1\nrule = B3/S23\nsize = 10x10\nalive_ratio = 0.5\ncolour = red".parse::<Manifest>().is_err());
+    }
+
+    #[test]
+    fn rejects_hexagonal_grids_whose_rows_do_not_fit_the_boundary() {
+        let text = |boundary: &str, row_n: u32| {
+            format!("seed = 1\nrule = B2/S34H\nboundary = {}\nsize = 10x{}\nalive_ratio = 0.5", boundary, row_n)
+        };
+        assert!(text("torus", 10).parse::<Manifest>().is_ok());
+        assert!(text("torus", 9).parse::<Manifest>().is_err());
+        assert!(text("klein", 9).parse::<Manifest>().is_ok());
+        assert!(text("klein", 10).parse::<Manifest>().is_err());
+        assert!(text("dead", 9).parse::<Manifest>().is_ok());
+    }
+
+    #[test]
+    fn replays_the_same_run() {
+        let manifest: Manifest = "# saved run\nseed = 99\nrule = B3/S23\nboundary = torus\nsize = 40x30\nalive_ratio = 0.3\ngeneration = 25\n"
+            .parse()
+            .unwrap();
+        let mut field = Field::with_seed(30, 40, 0.3, 99);
+        field.boundary = Boundary::Torus;
+        for _ in 0..25 {
+            field.iterate();
+        }
+        assert_eq!(states(&manifest.replay(ColourMode::default())), states(&field));
+        assert_eq!(states(&manifest.replay(ColourMode::default())), states(&manifest.replay(ColourMode::default())));
+    }
+
+    #[test]
+    fn replays_in_the_colour_mode_from_the_first_generation() {
+        let manifest: Manifest = "seed = 3\nrule = B3/S23\nsize = 30x20\nalive_ratio = 0.4\ngeneration = 12\n".parse().unwrap();
+        let mut field = Field::with_seed(20, 30, 0.4, 3);
+        field.colour_mode = ColourMode::QuadLife;
+        field.recolour(3);
+        for _ in 0..12 {
+            field.iterate();
+        }
+        let colours = |field: &Field| field.matrix.iter().flatten().map(|cell| cell.rgb).collect::<Vec<_>>();
+        assert_eq!(colours(&manifest.replay(ColourMode::QuadLife)), colours(&field));
+    }
+
+    #[test]
+    fn replays_with_the_recorded_backend() {
+        let text = |backend: &str| format!("seed = 7\nrule = B3/S23\nsize = 40x30\nalive_ratio = 0.3\ngeneration = 37\nbackend = {}\n", backend);
+        let replay = |backend: &str| states(&text(backend).parse::<Manifest>().unwrap().replay(ColourMode::default()));
+        // ビット演算のフィールドは密なフィールドと、HashLifeは端のないフィールドと同じ結果になる
+        assert_eq!(replay("bit-packed"), replay("dense"));
+        assert_eq!(replay("hashlife"), replay("sparse"));
+        assert_ne!(replay("sparse"), replay("dense"));
+        // 計算方法が扱えないルールは読み込めない
+        assert!(text("hashlife").replace("B3/S23", "B2/S34H").parse::<Manifest>().is_err());
+        assert!(text("quantum").parse::<Manifest>().is_err());
+    }
+}
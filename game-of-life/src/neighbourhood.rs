@@ -0,0 +1,178 @@
+// セルの近傍（どのセルを「接している」とみなすか）を表現するための列挙型
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Neighbourhood {
+    Moore { radius: u32 },      // 一辺 2r+1 の正方形（半径1なら周囲8セル）
+    VonNeumann { radius: u32 }, // マンハッタン距離r以内のひし形（半径1なら上下左右の4セル）
+    Hexagonal,                  // 奇数行を半セル右にずらした六角形グリッドの6セル
+}
+
+impl Default for Neighbourhood {
+    fn default() -> Neighbourhood {
+        Neighbourhood::Moore { radius: 1 }
+    }
+}
+
+impl Neighbourhood {
+    // 近傍の半径
+    pub fn radius(&self) -> u32 {
+        match self {
+            Neighbourhood::Moore { radius } | Neighbourhood::VonNeumann { radius } => *radius,
+            Neighbourhood::Hexagonal => 1,
+        }
+    }
+
+    // 中心を除いた近傍のセル数（接触数の最大値）
+    pub fn size(&self) -> u32 {
+        match self {
+            Neighbourhood::Moore { radius } => (2 * radius + 1).pow(2) - 1,
+            Neighbourhood::VonNeumann { radius } => 2 * radius * (radius + 1),
+            Neighbourhood::Hexagonal => 6,
+        }
+    }
+
+    // y行目のセルから見た近傍のセルの相対位置を返すメソッド（六角形グリッドでは行の偶奇で変わる）
+    pub fn offsets(&self, y: usize) -> Vec<(i32, i32)> {
+        let r = self.radius() as i32;
+        let mut offsets = vec![];
+        for offset_y in -r..=r {
+            for offset_x in -r..=r {
+                // 自分の位置であればスキップ
+                if offset_x == 0 && offset_y == 0 {
+                    continue;
+                }
+                let inside = match self {
+                    Neighbourhood::Moore { .. } => true,
+                    Neighbourhood::VonNeumann { .. } => offset_x.abs() + offset_y.abs() <= r,
+                    // 上下の行では、偶数行なら左寄りの2セル、奇数行なら右寄りの2セルが接する
+                    Neighbourhood::Hexagonal => {
+                        offset_y == 0 || offset_x == 0 || offset_x == if y % 2 == 1 { 1 } else { -1 }
+                    },
+                };
+                if inside {
+                    offsets.push((offset_x, offset_y));
+                }
+            }
+        }
+        offsets
+    }
+
+    // すべてのセルについて、近傍の生存セルの数を col_n x row_n の行列で返すメソッド
+    // alive(x, y) は領域外を含む座標の生存セルを1、それ以外を0として返す関数
+    // 半径が大きくても速く数えられるよう、Mooreは累積和テーブルを、von Neumannは行ごとの累積和を使う
+    pub fn count_all<F>(&self, col_n: u32, row_n: u32, alive: F) -> Vec<Vec<u32>>
+    where
+        F: Fn(i32, i32) -> u32,
+    {
+        let (w, h) = (col_n as usize, row_n as usize);
+        let r = self.radius() as usize;
+        if let Neighbourhood::Hexagonal = self {
+            let offsets = [self.offsets(0), self.offsets(1)];
+            return (0..w).map(|x| {
+                (0..h).map(|y| {
+                    offsets[y % 2].iter()
+                        .map(|&(dx, dy)| alive(x as i32 + dx, y as i32 + dy))
+                        .sum()
+                }).collect()
+            }).collect();
+        }
+
+        // 周囲r セル分を境界に応じて埋めたグリッドを作り、行ごとの累積和を求める
+        // row_sums[py][px] は padded の py 行目の 0..px 列の合計
+        let (pw, ph) = (w + 2 * r, h + 2 * r);
+        let mut row_sums = vec![vec![0u32; pw + 1]; ph];
+        for (py, sums) in row_sums.iter_mut().enumerate() {
+            for px in 0..pw {
+                let value = alive(px as i32 - r as i32, py as i32 - r as i32);
+                sums[px + 1] = sums[px] + value;
+            }
+        }
+
+        match self {
+            Neighbourhood::Moore { .. } => {
+                // table[py][px] は padded の 0..py 行・0..px 列の合計（累積和テーブル）
+                let mut table = vec![vec![0u32; pw + 1]; ph + 1];
+                for py in 0..ph {
+                    for px in 0..=pw {
+                        table[py + 1][px] = table[py][px] + row_sums[py][px];
+                    }
+                }
+                (0..w).map(|x| {
+                    (0..h).map(|y| {
+                        // 中心が (x + r, y + r) にある一辺 2r+1 の正方形の合計から中心を引く
+                        let (x0, y0, x1, y1) = (x, y, x + 2 * r + 1, y + 2 * r + 1);
+                        let total = table[y1][x1] + table[y0][x0] - table[y0][x1] - table[y1][x0];
+                        total - alive(x as i32, y as i32)
+                    }).collect()
+                }).collect()
+            },
+            Neighbourhood::VonNeumann { .. } => {
+                (0..w).map(|x| {
+                    (0..h).map(|y| {
+                        // 各行について、中心からの縦の距離に応じた幅の区間を足し合わせる
+                        let total: u32 = (0..=2 * r).map(|py| {
+                            let half = r - (py as i32 - r as i32).unsigned_abs() as usize;
+                            let (x0, x1) = (x + r - half, x + r + half + 1);
+                            row_sums[y + py][x1] - row_sums[y + py][x0]
+                        }).sum();
+                        total - alive(x as i32, y as i32)
+                    }).collect()
+                }).collect()
+            },
+            Neighbourhood::Hexagonal => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    const ALL: [Neighbourhood; 6] = [
+        Neighbourhood::Moore { radius: 1 },
+        Neighbourhood::Moore { radius: 3 },
+        Neighbourhood::VonNeumann { radius: 1 },
+        Neighbourhood::VonNeumann { radius: 4 },
+        Neighbourhood::Hexagonal,
+        Neighbourhood::Moore { radius: 5 },
+    ];
+
+    #[test]
+    fn offsets_match_the_neighbourhood_size() {
+        for neighbourhood in ALL {
+            assert_eq!(neighbourhood.offsets(0).len() as u32, neighbourhood.size());
+            assert_eq!(neighbourhood.offsets(1).len() as u32, neighbourhood.size());
+        }
+        assert_eq!(Neighbourhood::VonNeumann { radius: 1 }.offsets(0), vec![(0, -1), (-1, 0), (1, 0), (0, 1)]);
+        assert_eq!(Neighbourhood::Hexagonal.offsets(0), vec![(-1, -1), (0, -1), (-1, 0), (1, 0), (-1, 1), (0, 1)]);
+        assert_eq!(Neighbourhood::Hexagonal.offsets(1), vec![(0, -1), (1, -1), (-1, 0), (1, 0), (0, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn count_all_matches_direct_counting() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let (col_n, row_n) = (23u32, 17u32);
+        let grid: Vec<Vec<u32>> = (0..col_n)
+            .map(|_| (0..row_n).map(|_| rng.gen_bool(0.4) as u32).collect())
+            .collect();
+        // 領域外は死んでいるものとみなす
+        let alive = |x: i32, y: i32| {
+            if x < 0 || col_n as i32 <= x || y < 0 || row_n as i32 <= y {
+                0
+            } else {
+                grid[x as usize][y as usize]
+            }
+        };
+        for neighbourhood in ALL {
+            let counts = neighbourhood.count_all(col_n, row_n, alive);
+            for (x, col) in counts.iter().enumerate() {
+                for (y, &count) in col.iter().enumerate() {
+                    let direct: u32 = neighbourhood.offsets(y).iter()
+                        .map(|&(dx, dy)| alive(x as i32 + dx, y as i32 + dy))
+                        .sum();
+                    assert_eq!(count, direct, "{:?} at ({}, {})", neighbourhood, x, y);
+                }
+            }
+        }
+    }
+}
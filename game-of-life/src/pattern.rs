@@ -0,0 +1,372 @@
+use std::fs;
+use std::path::Path;
+
+use crate::rule::Rule;
+
+// RLEや.cellsファイルから読み込んだパターンを表現するための構造体
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pattern {
+    pub width: usize,               // パターンの幅（列数）
+    pub height: usize,              // パターンの高さ（行数）
+    pub cells: Vec<(usize, usize)>, // 死んでいないセルの座標(x, y)。左上が(0, 0)
+    pub states: Vec<u8>,            // cellsのセルそれぞれの状態番号（1が生存。多状態のRLEでは1以上の番号）
+    pub rule: Option<Rule>,         // RLEのヘッダーに書かれていたルール
+}
+
+// RLEを書き出すときの1行あたりの最大文字数
+const RLE_LINE_LENGTH: usize = 70;
+
+// スタンプとして置ける、組み込みのパターンのカタログ（名前, RLE）
+pub const CATALOGUE: [(&str, &str); 6] = [
+    ("glider", "x = 3, y = 3\nbob$2bo$3o!"),
+    ("LWSS", "x = 5, y = 4\nbo2bo$o$o3bo$4o!"),
+    ("Gosper glider gun", "x = 36, y = 9\n24bo$22bobo$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o$2o8bo3bob2o4bobo$10bo5bo7bo$11bo3bo$12b2o!"),
+    ("R-pentomino", "x = 3, y = 3\nb2o$2o$bo!"),
+    ("acorn", "x = 7, y = 3\nbo$3bo$2o2b3o!"),
+    ("pulsar", "x = 13, y = 13\n2b3o3b3o2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2$2b3o3b3o$o4bobo4bo$o4bobo4bo$o4bobo4bo2$2b3o3b3o!"),
+];
+
+impl Pattern {
+    // カタログのindex番目のパターンを読み込むためのメソッド
+    pub fn from_catalogue(index: usize) -> Option<Pattern> {
+        let (_, rle) = CATALOGUE.get(index)?;
+        Some(Pattern::from_rle(rle).unwrap())
+    }
+
+    // 拡張子（.rle または .cells）に応じてファイルからパターンを読み込むためのメソッド
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Pattern, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("cells") => Pattern::from_plaintext(&text),
+            _ => Pattern::from_rle(&text),
+        }
+    }
+
+    // Golly/LifeWiki形式のRLE文字列からパターンを読み込むためのメソッド
+    pub fn from_rle(text: &str) -> Result<Pattern, String> {
+        let mut lines = text.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        // "x = 3, y = 3, rule = B3/S23" 形式のヘッダー
+        let header = lines.next().ok_or("RLE has no header line")?;
+        let (mut width, mut height, mut rule) = (None, None, None);
+        for item in header.split(',') {
+            let (key, value) = item.split_once('=')
+                .ok_or_else(|| format!("invalid RLE header item {:?}", item))?;
+            let value = value.trim();
+            match key.trim() {
+                "x" => width = Some(value.parse::<usize>().map_err(|err| format!("invalid RLE width {:?}: {}", value, err))?),
+                "y" => height = Some(value.parse::<usize>().map_err(|err| format!("invalid RLE height {:?}: {}", value, err))?),
+                "rule" => rule = Some(value.parse::<Rule>()?),
+                _ => {},
+            }
+        }
+        let width = width.ok_or("RLE header is missing x")?;
+        let height = height.ok_or("RLE header is missing y")?;
+
+        // 本体は "<個数><タグ>" の並びで、b が死、o（およびその他の小文字）が生、$ が改行、! が終端
+        // 多状態のRLEでは . が状態0、A〜X が状態1〜24で、p〜y を前に付けると25以上の状態になる（"pA" が25）
+        let (mut cells, mut states) = (vec![], vec![]);
+        let (mut x, mut y) = (0usize, 0usize);
+        let mut run_count = 0usize;
+        let mut prefix = 0u8;
+        'body: for line in lines {
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                if let Some(digit) = c.to_digit(10) {
+                    run_count = run_count.checked_mul(10)
+                        .and_then(|count| count.checked_add(digit as usize))
+                        .ok_or("RLE run count is too large")?;
+                    continue;
+                }
+                if ('p'..='y').contains(&c) && chars.peek().is_some_and(|next| next.is_ascii_uppercase()) {
+                    prefix = c as u8 - b'p' + 1;
+                    continue;
+                }
+                let run = run_count.max(1);
+                run_count = 0;
+                let state = match c {
+                    '!' => break 'body,
+                    '$' => {
+                        x = 0;
+                        y = y.saturating_add(run);
+                        continue;
+                    },
+                    'b' | '.' => 0,
+                    c if c.is_whitespace() => continue,
+                    'A'..='X' => {
+                        let state = prefix as usize * 24 + (c as u8 - b'A') as usize + 1;
+                        prefix = 0;
+                        u8::try_from(state).map_err(|_| format!("RLE state {} is larger than 255", state))?
+                    },
+                    c if c.is_ascii_lowercase() => 1,
+                    c => return Err(format!("unexpected character {:?} in RLE body", c)),
+                };
+                // 宣言した大きさからはみ出すセルは、セルを追加する前にエラーにする（巨大な個数でメモリを使い果たさないように）
+                if state != 0 {
+                    if height <= y || width < x.saturating_add(run) {
+                        let x = if height <= y { x } else { x.max(width) };
+                        return Err(format!("RLE cell ({}, {}) lies outside the declared {}x{} bounds", x, y, width, height));
+                    }
+                    for i in 0..run {
+                        cells.push((x + i, y));
+                        states.push(state);
+                    }
+                }
+                x = x.saturating_add(run);
+            }
+        }
+
+        Ok(Pattern { width, height, cells, states, rule })
+    }
+
+    // "." が死、"O" が生のプレーンテキスト（.cells）形式からパターンを読み込むためのメソッド
+    pub fn from_plaintext(text: &str) -> Result<Pattern, String> {
+        let rows: Vec<&str> = text.lines()
+            .map(|line| line.trim_end())
+            .filter(|line| !line.starts_with('!'))
+            .collect();
+
+        let mut cells = vec![];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                match c {
+                    '.' => {},
+                    'O' | '*' => cells.push((x, y)),
+                    c => return Err(format!("unexpected character {:?} in plaintext pattern", c)),
+                }
+            }
+        }
+        let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+        let states = vec![1; cells.len()];
+        Ok(Pattern { width, height: rows.len(), cells, states, rule: None })
+    }
+
+    // パターンを時計回りに90°回転させたパターンを返すメソッド
+    pub fn rotated(&self) -> Pattern {
+        let cells = self.cells.iter().map(|&(x, y)| (self.height - 1 - y, x)).collect();
+        Pattern { width: self.height, height: self.width, cells, ..self.clone() }.sorted()
+    }
+
+    // パターンを左右に反転させたパターンを返すメソッド
+    pub fn flipped(&self) -> Pattern {
+        let cells = self.cells.iter().map(|&(x, y)| (self.width - 1 - x, y)).collect();
+        Pattern { cells, ..self.clone() }.sorted()
+    }
+
+    // セルをRLEを読み込んだときと同じ順番（行ごとに左から右）に、状態番号と一緒に並べ直す
+    fn sorted(self) -> Pattern {
+        let mut cells: Vec<((usize, usize), u8)> = self.cells.into_iter().zip(self.states).collect();
+        cells.sort_by_key(|&((x, y), _)| (y, x));
+        let (cells, states) = cells.into_iter().unzip();
+        Pattern { cells, states, ..self }
+    }
+
+    // パターンをRLE文字列に変換するためのメソッド。ルールがあればヘッダーに含める
+    // 状態番号が2以上のセルがあれば、多状態のRLEとして書き出す
+    pub fn to_rle(&self) -> String {
+        let mut header = format!("x = {}, y = {}", self.width, self.height);
+        if let Some(rule) = self.rule {
+            header += &format!(", rule = {}", rule);
+        }
+
+        // 死んでいないセルを行ごとに左から右へ並べる。ヘッダーの大きさの格子は作らない
+        let mut cells: Vec<((usize, usize), u8)> = self.cells.iter().copied().zip(self.states.iter().copied())
+            .filter(|&(_, state)| state != 0)
+            .collect();
+        cells.sort_by_key(|&((x, y), _)| (y, x));
+        let multi_state = cells.iter().any(|&(_, state)| state > 1);
+
+        // 行ごとに連続する同じ状態をまとめる。行末の死んだセルは書かず、空行は "n$" でまとめる
+        // （先頭の空行も位置を保つために書き出す）
+        let mut tokens: Vec<String> = vec![];
+        let flush = |tokens: &mut Vec<String>, (run, state): (usize, u8)| {
+            if 0 < run {
+                tokens.push(run_token(run, &state_tag(state, multi_state)));
+            }
+        };
+        let (mut row, mut next_x) = (0, 0); // いま書いている行と、次に書く列
+        let mut run = (0, 0);               // まだ書いていない同じ状態の並び（長さ, 状態番号）
+        for ((x, y), state) in cells {
+            if y != row {
+                flush(&mut tokens, run);
+                tokens.push(run_token(y - row, "$"));
+                (row, next_x, run) = (y, 0, (0, 0));
+            } else if x < next_x {
+                continue;
+            }
+            if next_x < x || run.1 != state {
+                flush(&mut tokens, run);
+                flush(&mut tokens, (x - next_x, 0));
+                run = (0, state);
+            }
+            run.0 += 1;
+            next_x = x + 1;
+        }
+        flush(&mut tokens, run);
+        tokens.push("!".to_string());
+
+        // 1行がRLE_LINE_LENGTHを超えないように折り返す
+        let mut body = vec![String::new()];
+        for token in tokens {
+            if RLE_LINE_LENGTH < body.last().unwrap().len() + token.len() {
+                body.push(String::new());
+            }
+            body.last_mut().unwrap().push_str(&token);
+        }
+        format!("{}\n{}\n", header, body.join("\n"))
+    }
+}
+
+// 個数とタグから "3o" のようなRLEのトークンを作る（個数が1なら省略）
+fn run_token(run: usize, tag: &str) -> String {
+    if run == 1 {
+        tag.to_string()
+    } else {
+        format!("{}{}", run, tag)
+    }
+}
+
+// 状態番号stateのセルを表すRLEのタグ（2状態では b か o、多状態では . か A〜X（25以上は pA のような2文字））
+fn state_tag(state: u8, multi_state: bool) -> String {
+    match (state, multi_state) {
+        (0, false) => "b".to_string(),
+        (_, false) => "o".to_string(),
+        (0, true) => ".".to_string(),
+        (1..=24, true) => ((b'A' + state - 1) as char).to_string(),
+        (_, true) => {
+            let (prefix, letter) = ((state - 25) / 24, (state - 25) % 24);
+            format!("{}{}", (b'p' + prefix) as char, (b'A' + letter) as char)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIDER_RLE: &str = "#N Glider\n#C A comment\nx = 3, y = 3, rule = B3/S23\nbob$2bo$3o!\n";
+
+    #[test]
+    fn reads_rle() {
+        let glider = Pattern::from_rle(GLIDER_RLE).unwrap();
+        assert_eq!(glider.width, 3);
+        assert_eq!(glider.height, 3);
+        assert_eq!(glider.cells, vec![(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+        assert_eq!(glider.rule, Some(Rule::conway()));
+    }
+
+    #[test]
+    fn reads_multi_line_rle_with_blank_rows() {
+        let pattern = Pattern::from_rle("x = 12, y = 4\n2o$\n\n3bo2$\n11o!").unwrap();
+        assert_eq!(pattern.rule, None);
+        assert_eq!(pattern.cells.len(), 2 + 1 + 11);
+        assert!(pattern.cells.contains(&(3, 1)));
+        assert!(pattern.cells.contains(&(10, 3)));
+    }
+
+    #[test]
+    fn rejects_malformed_rle() {
+        assert!(Pattern::from_rle("").is_err());
+        assert!(Pattern::from_rle("x = 2\noo!").is_err());
+        assert!(Pattern::from_rle("x = 2, y = 1\n3o!").is_err());
+        assert!(Pattern::from_rle("x = 2, y = 1, rule = B3/Q23\noo!").is_err());
+    }
+
+    #[test]
+    fn rejects_huge_run_counts_without_allocating() {
+        let overflow = Pattern::from_rle("x = 2, y = 1\n99999999999999999999999o!").err().unwrap();
+        assert!(overflow.contains("too large"), "{}", overflow);
+        let huge = Pattern::from_rle("x = 2, y = 1\n999999999999o!").err().unwrap();
+        assert!(huge.contains("(2, 0) lies outside the declared 2x1 bounds"), "{}", huge);
+        assert!(Pattern::from_rle("x = 2, y = 1\n999999999999$o!").is_err());
+        // 末尾の空の行や死んだセルの並びは、大きさを超えていても構わない
+        assert_eq!(Pattern::from_rle("x = 2, y = 1\n2o5b3$!").unwrap().cells, vec![(0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn writes_and_places_huge_declared_patterns_without_allocating() {
+        let huge = Pattern::from_rle("x = 100000000, y = 100000000\n$3bo!").unwrap();
+        assert_eq!(huge.to_rle(), "x = 100000000, y = 100000000\n$3bo!\n");
+
+        // フィールドに重なる部分だけを置く。中央に置くと、セルはフィールドの外に出る
+        let mut field = crate::Field::new(4, 5, 0.0);
+        field.place_pattern(&huge, 0, 0);
+        assert!(field.alive_at(3, 1));
+        field.place_pattern_centered(&huge);
+        assert_eq!(field.population(), 0);
+    }
+
+    #[test]
+    fn reads_plaintext() {
+        let glider = Pattern::from_plaintext("!Name: Glider\n!\n.O\n..O\nOOO\n").unwrap();
+        assert_eq!((glider.width, glider.height), (3, 3));
+        assert_eq!(glider.cells, vec![(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+        assert!(Pattern::from_plaintext("O?O").is_err());
+    }
+
+    #[test]
+    fn writes_rle_that_reads_back() {
+        let glider = Pattern::from_rle(GLIDER_RLE).unwrap();
+        assert_eq!(glider.to_rle(), "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n");
+
+        let sparse = Pattern { width: 200, height: 6, cells: vec![(0, 2), (199, 2), (5, 5)], states: vec![1; 3], rule: None };
+        let rle = sparse.to_rle();
+        assert!(rle.lines().all(|line| line.len() <= RLE_LINE_LENGTH));
+        assert_eq!(Pattern::from_rle(&rle).unwrap(), sparse);
+    }
+
+    #[test]
+    fn reads_and_writes_multi_state_rle() {
+        // Wireworldの電子（頭A・尾B）が導体Cの上を進む
+        let pattern = Pattern::from_rle("x = 6, y = 2, rule = WireWorld\nBA4C$.2pA2.yO!").unwrap();
+        assert_eq!(pattern.rule, Some(Rule::wireworld()));
+        assert_eq!(pattern.cells, vec![(0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0), (1, 1), (2, 1), (5, 1)]);
+        assert_eq!(pattern.states, vec![2, 1, 3, 3, 3, 3, 25, 25, 255]);
+        assert_eq!(pattern.to_rle(), "x = 6, y = 2, rule = WireWorld\nBA4C$.2pA2.yO!\n");
+        assert!(Pattern::from_rle("x = 1, y = 1\nyX!").is_err());
+
+        // 2状態のRLEの大文字でないタグは、これまでどおり生存として読む
+        let two_state = Pattern::from_rle("x = 3, y = 1\nbxo!").unwrap();
+        assert_eq!(two_state.states, vec![1, 1]);
+    }
+
+    #[test]
+    fn catalogue_patterns_are_valid_rle() {
+        let populations: Vec<usize> = (0..CATALOGUE.len())
+            .map(|i| Pattern::from_catalogue(i).unwrap().cells.len())
+            .collect();
+        assert_eq!(populations, vec![5, 9, 36, 5, 7, 48]);
+        assert_eq!(Pattern::from_catalogue(CATALOGUE.len()), None);
+    }
+
+    #[test]
+    fn rotates_and_flips() {
+        let glider = Pattern::from_catalogue(0).unwrap();
+        // .O.      O..      ..O
+        // ..O  ->  O.O  ->  O.O  （時計回りに90°回転、さらに左右反転）
+        // OOO      OO.      .OO
+        let rotated = glider.rotated();
+        assert_eq!(rotated.cells, vec![(0, 0), (0, 1), (2, 1), (0, 2), (1, 2)]);
+        assert_eq!(rotated.states, vec![1; 5]);
+        assert_eq!(rotated.flipped().cells, vec![(2, 0), (0, 1), (2, 1), (1, 2), (2, 2)]);
+
+        let lwss = Pattern::from_catalogue(1).unwrap();
+        assert_eq!((lwss.rotated().width, lwss.rotated().height), (4, 5));
+        assert_eq!(lwss.rotated().rotated().rotated().rotated(), lwss);
+        assert_eq!(lwss.flipped().flipped(), lwss);
+    }
+
+    #[test]
+    fn glider_gun_emits_a_glider_every_30_generations() {
+        let mut field = crate::Field::new(40, 60, 0.0);
+        field.place_pattern(&Pattern::from_catalogue(2).unwrap(), 1, 1);
+        for _ in 0..30 {
+            field.iterate();
+        }
+        assert_eq!(field.population(), 36 + 5);
+    }
+}
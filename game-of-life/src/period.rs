@@ -0,0 +1,138 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::{CellState, Field};
+
+// フィールドが落ち着いた先の状態
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Extinct,                      // すべてのセルが死滅した
+    StillLife,                    // 1世代ごとに同じ状態を繰り返す（周期1）
+    Oscillator { period: usize }, // period世代ごとに同じ状態を繰り返す
+}
+
+impl Outcome {
+    // 同じ状態を繰り返す周期（世代数）
+    pub fn period(&self) -> usize {
+        match self {
+            Outcome::Extinct | Outcome::StillLife => 1,
+            Outcome::Oscillator { period } => *period,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Extinct => write!(f, "extinct"),
+            Outcome::StillLife => write!(f, "still life"),
+            Outcome::Oscillator { period } => write!(f, "period {} oscillator", period),
+        }
+    }
+}
+
+// 世代ごとのフィールドのハッシュ値を覚えておき、同じ状態が繰り返し現れたことを検出するための構造体
+pub struct PeriodDetector {
+    max_period: usize,     // 検出する周期の上限
+    recent: VecDeque<u64>, // 直近max_period世代のハッシュ値（先頭が最も古い）
+}
+
+impl PeriodDetector {
+    // max_period世代までの周期を検出する構造体を作るためのメソッド
+    pub fn new(max_period: usize) -> PeriodDetector {
+        PeriodDetector { max_period, recent: VecDeque::new() }
+    }
+
+    // これまでに観測した世代を忘れるためのメソッド（フィールドを書き換えたときに使う）
+    pub fn reset(&mut self) {
+        self.recent.clear();
+    }
+
+    // 新しい世代のフィールドを観測し、直近の世代と同じ状態になっていれば落ち着いた先の状態を返すメソッド
+    pub fn observe(&mut self, field: &Field) -> Option<Outcome> {
+        let hash = hash_field(field);
+        // 最も近い過去に同じハッシュ値が現れた世代との差が周期になる
+        let period = self.recent.iter().rev().position(|&seen| seen == hash).map(|i| i + 1);
+        self.recent.push_back(hash);
+        if self.max_period < self.recent.len() {
+            self.recent.pop_front();
+        }
+
+        match period? {
+            1 if field.matrix.iter().flatten().all(|cell| cell.state == CellState::Dead) => Some(Outcome::Extinct),
+            1 => Some(Outcome::StillLife),
+            period => Some(Outcome::Oscillator { period }),
+        }
+    }
+}
+
+// フィールドのすべてのセルの状態からハッシュ値を求める
+fn hash_field(field: &Field) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for cell in field.matrix.iter().flatten() {
+        cell.state.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 指定した座標のセルだけが生きているフィールドを生成する
+    fn field_with(alive: &[(usize, usize)]) -> Field {
+        let mut field = Field::new(8, 8, 0.0);
+        for &(x, y) in alive {
+            field.matrix[x][y].state = CellState::Alive;
+        }
+        field
+    }
+
+    // 落ち着いた先の状態が見つかるまで（最大generations世代）進める
+    fn settle(field: &mut Field, generations: usize) -> Option<Outcome> {
+        let mut detector = PeriodDetector::new(16);
+        detector.observe(field);
+        for _ in 0..generations {
+            field.iterate();
+            if let Some(outcome) = detector.observe(field) {
+                return Some(outcome);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn detects_a_blinker() {
+        let mut blinker = field_with(&[(2, 3), (3, 3), (4, 3)]);
+        assert_eq!(settle(&mut blinker, 10), Some(Outcome::Oscillator { period: 2 }));
+    }
+
+    #[test]
+    fn detects_a_block_and_a_beehive() {
+        let mut block = field_with(&[(3, 3), (3, 4), (4, 3), (4, 4)]);
+        assert_eq!(settle(&mut block, 10), Some(Outcome::StillLife));
+
+        let mut beehive = field_with(&[(2, 3), (3, 2), (4, 2), (5, 3), (3, 4), (4, 4)]);
+        assert_eq!(settle(&mut beehive, 10), Some(Outcome::StillLife));
+        assert_eq!(Outcome::StillLife.period(), 1);
+    }
+
+    #[test]
+    fn detects_extinction() {
+        let mut lonely = field_with(&[(3, 3), (5, 5)]);
+        assert_eq!(settle(&mut lonely, 10), Some(Outcome::Extinct));
+    }
+
+    #[test]
+    fn ignores_periods_longer_than_the_limit() {
+        // 周期2のブリンカーも、周期1までしか見ないと検出されない
+        let mut blinker = field_with(&[(2, 3), (3, 3), (4, 3)]);
+        let mut detector = PeriodDetector::new(1);
+        for _ in 0..10 {
+            blinker.iterate();
+            assert_eq!(detector.observe(&blinker), None);
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::neighbourhood::Neighbourhood;
+use crate::rule::Rule;
+use crate::{Cell, CellState, Field};
+
+// 背景の色（ウィンドウに描くときと同じ色）
+pub const BACKGROUND: [f32; 3] = [0.11, 0.12, 0.13];
+
+const AGE_RAMP_GENERATIONS: f32 = 100.0;     // 年齢の色の変化が一番古い色にたどり着くまでの世代数
+pub const GHOST_TRAIL_GENERATIONS: u32 = 30; // 死んだセルの残像が消えるまでの世代数
+const GHOST_TRAIL_ALPHA: f32 = 0.5;          // 死んだ直後のセルの残像の不透明度
+
+// Wireworldのセルの色（Gollyと同じく、電子の頭が青、尾が白、導体がオレンジ）。描き方によらず同じ色で描く
+const HEAD_COLOUR: [f32; 3] = [0.0, 0.5, 1.0];
+const TAIL_COLOUR: [f32; 3] = [1.0, 1.0, 1.0];
+const CONDUCTOR_COLOUR: [f32; 3] = [1.0, 0.5, 0.0];
+
+// 年齢の色の変化（生まれたばかりの白っぽい黄色から、赤を経て、古いセルの紫へ）
+const AGE_RAMP: [[f32; 3]; 4] = [[1.0, 1.0, 0.8], [1.0, 0.6, 0.0], [0.8, 0.1, 0.1], [0.35, 0.1, 0.5]];
+
+// セルの描き方を表現するための列挙型
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum RenderMode {
+    #[default]
+    Colour, // セルそれぞれの色で描く
+    Age,    // 生きているセルを、生まれてからの世代数に応じた色で描く（ヒートマップ）
+    Ghost,  // セルそれぞれの色に加えて、最近死んだセルを薄れていく残像として描く
+}
+
+// Vキーで切り替える順番
+pub const ALL: [RenderMode; 3] = [RenderMode::Colour, RenderMode::Age, RenderMode::Ghost];
+
+impl RenderMode {
+    // ルールruleのフィールドにあるセルを描く色（RGBA）を返すメソッド。何も描かないセルではNoneを返す
+    pub fn colour(&self, cell: &Cell, rule: &Rule) -> Option<[f32; 4]> {
+        let [r, g, b] = match (self, cell.state) {
+            (RenderMode::Ghost, CellState::Dead) if cell.dead_for < GHOST_TRAIL_GENERATIONS => {
+                let [r, g, b] = cell.rgb;
+                let fade = 1.0 - cell.dead_for as f32 / GHOST_TRAIL_GENERATIONS as f32;
+                return Some([r, g, b, GHOST_TRAIL_ALPHA * fade]);
+            },
+            (_, CellState::Dead) => return None,
+            (_, CellState::Head) => HEAD_COLOUR,
+            (_, CellState::Tail) => TAIL_COLOUR,
+            (_, CellState::Conductor) => CONDUCTOR_COLOUR,
+            (RenderMode::Age, _) => age_colour(cell.age),
+            _ => cell.rgb,
+        };
+        // 死につつあるセルは状態に応じて薄れていく
+        Some([r, g, b, rule.intensity(cell.state)])
+    }
+
+    // ALLの中で次のモードを返すメソッド
+    pub fn next(&self) -> RenderMode {
+        let i = ALL.iter().position(|mode| mode == self).unwrap();
+        ALL[(i + 1) % ALL.len()]
+    }
+}
+
+impl fmt::Display for RenderMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            RenderMode::Colour => "colour",
+            RenderMode::Age => "age",
+            RenderMode::Ghost => "ghost",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for RenderMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<RenderMode, String> {
+        ALL.iter()
+            .find(|mode| mode.to_string().eq_ignore_ascii_case(s.trim()))
+            .copied()
+            .ok_or_else(|| format!("unknown render mode {:?}: expected one of colour, age, ghost", s))
+    }
+}
+
+// 生まれてからage世代のセルの色を、AGE_RAMPの色の間を補間して求める
+fn age_colour(age: u32) -> [f32; 3] {
+    let position = (age as f32 / AGE_RAMP_GENERATIONS).min(1.0) * (AGE_RAMP.len() - 1) as f32;
+    let i = (position as usize).min(AGE_RAMP.len() - 2);
+    let t = position - i as f32;
+    let (from, to) = (AGE_RAMP[i], AGE_RAMP[i + 1]);
+    [0, 1, 2].map(|channel| from[channel] + (to[channel] - from[channel]) * t)
+}
+
+// GPUを使わずにフィールドを描いた画像（1画素あたりRGBの3バイト）
+pub struct Image {
+    pub width: u32,      // 画像の幅（ピクセル）
+    pub height: u32,     // 画像の高さ（ピクセル）
+    pub pixels: Vec<u8>, // 左上から行ごとに並べた画素の色
+}
+
+impl Image {
+    // フィールドを1セルあたりcell_sizeピクセルの正方形として、modeの描き方で描くためのメソッド
+    // 六角形グリッドでは、ウィンドウと同じく奇数行を半セル右にずらす
+    pub fn render(field: &Field, cell_size: u32, mode: RenderMode) -> Image {
+        let hexagonal = field.rule.neighbourhood == Neighbourhood::Hexagonal;
+        let extra = if hexagonal { cell_size / 2 } else { 0 };
+        let width = field.col_n * cell_size + extra;
+        let height = field.row_n * cell_size;
+        let mut image = Image { width, height, pixels: vec![0; (width * height * 3) as usize] };
+        image.fill(0, 0, width, height, BACKGROUND, 1.0);
+        for (x, col) in field.matrix.iter().enumerate() {
+            for (y, cell) in col.iter().enumerate() {
+                let Some([r, g, b, alpha]) = mode.colour(cell, &field.rule) else {
+                    continue;
+                };
+                let row_shift = if hexagonal && y % 2 == 1 { cell_size / 2 } else { 0 };
+                image.fill(x as u32 * cell_size + row_shift, y as u32 * cell_size, cell_size, cell_size, [r, g, b], alpha);
+            }
+        }
+        image
+    }
+
+    // (x, y)の画素の色
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        let i = ((y * self.width + x) * 3) as usize;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]]
+    }
+
+    // 画像をPNGファイルとして書き出すためのメソッド
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let failed = |err: &dyn std::fmt::Display| format!("failed to save {}: {}", path.display(), err);
+        let file = File::create(path).map_err(|err| failed(&err))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|err| failed(&err))?;
+        writer.write_image_data(&self.pixels).map_err(|err| failed(&err))
+    }
+
+    // 左上が(left, top)の長方形を、不透明度alphaの色rgbで塗るためのメソッド
+    fn fill(&mut self, left: u32, top: u32, w: u32, h: u32, rgb: [f32; 3], alpha: f32) {
+        for y in top..(top + h).min(self.height) {
+            for x in left..(left + w).min(self.width) {
+                let i = ((y * self.width + x) * 3) as usize;
+                for (channel, value) in self.pixels[i..i + 3].iter_mut().zip(rgb) {
+                    let under = *channel as f32 / 255.0;
+                    *channel = ((value * alpha + under * (1.0 - alpha)) * 255.0).round() as u8;
+                }
+            }
+        }
+    }
+}
+
+// セル座標(x0, y0)を左上とする width×height セルの範囲を、1セル1テクセルのRGBA（左上から行ごと）で描いたバイト列を返す関数
+// ウィンドウでテクスチャとして拡大して描くためのもので、範囲の外のセルは無視し、何も描かないテクセルは透明にする
+// 六角形グリッドの行のずれは表せないため、六角形グリッドでは使わない
+pub fn texels<'a, I>(cells: I, rule: &Rule, mode: RenderMode, (x0, y0): (i64, i64), width: u32, height: u32) -> Vec<u8>
+where
+    I: IntoIterator<Item = ((i64, i64), &'a Cell)>,
+{
+    let mut texels = vec![0; (width * height * 4) as usize];
+    for ((x, y), cell) in cells {
+        let (x, y) = (x - x0, y - y0);
+        if x < 0 || y < 0 || width as i64 <= x || height as i64 <= y {
+            continue;
+        }
+        if let Some(rgba) = mode.colour(cell, rule) {
+            let i = ((y * width as i64 + x) * 4) as usize;
+            texels[i..i + 4].copy_from_slice(&rgba.map(|channel| (channel * 255.0).round() as u8));
+        }
+    }
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Rule;
+
+    #[test]
+    fn draws_live_cells_over_the_background() {
+        let mut field = Field::new(3, 4, 0.0);
+        field.matrix[1][2].state = CellState::Alive;
+        field.matrix[1][2].rgb = [1.0, 0.5, 0.0];
+        let image = Image::render(&field, 2, RenderMode::Colour);
+        assert_eq!((image.width, image.height), (8, 6));
+        assert_eq!(image.pixel(0, 0), [28, 31, 33]);
+        assert_eq!(image.pixel(2, 4), [255, 128, 0]);
+        assert_eq!(image.pixel(3, 5), [255, 128, 0]);
+        assert_eq!(image.pixel(4, 4), [28, 31, 33]);
+    }
+
+    #[test]
+    fn dying_cells_fade_and_hexagonal_rows_are_shifted() {
+        let mut field = Field::new(2, 2, 0.0);
+        field.rule = "B2/S/C3".parse::<Rule>().unwrap();
+        field.matrix[0][0].state = CellState::Dying(1);
+        field.matrix[0][0].rgb = [1.0, 1.0, 1.0];
+        let image = Image::render(&field, 1, RenderMode::Colour);
+        assert_eq!(image.pixel(0, 0), [142, 143, 144]);
+
+        field.rule.neighbourhood = Neighbourhood::Hexagonal;
+        field.matrix[0][1].state = CellState::Alive;
+        field.matrix[0][1].rgb = [1.0, 1.0, 1.0];
+        let image = Image::render(&field, 2, RenderMode::Colour);
+        assert_eq!(image.width, 5);
+        assert_eq!(image.pixel(0, 2), [28, 31, 33]);
+        assert_eq!(image.pixel(1, 2), [255, 255, 255]);
+    }
+
+    #[test]
+    fn saves_a_png() {
+        let path = std::env::temp_dir().join(format!("game-of-life-render-{}.png", std::process::id()));
+        let image = Image::render(&Field::new(5, 7, 0.5), 3, RenderMode::Colour);
+        image.save_png(&path).unwrap();
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(pixels, image.pixels);
+    }
+
+    #[test]
+    fn age_mode_ramps_from_newborn_to_old() {
+        let rule = Rule::conway();
+        let mut cell = Cell::new(CellState::Dead, 0.0, 0.5, 1.0).alive_clone();
+        assert_eq!(RenderMode::Age.colour(&cell, &rule), Some([1.0, 1.0, 0.8, 1.0]));
+        for _ in 0..1000 {
+            cell = cell.alive_clone();
+        }
+        assert_eq!(cell.age, 1000);
+        let [r, g, b, _] = RenderMode::Age.colour(&cell, &rule).unwrap();
+        assert!((r - 0.35).abs() < 1e-6 && (g - 0.1).abs() < 1e-6 && (b - 0.5).abs() < 1e-6);
+        assert_eq!(RenderMode::Colour.colour(&cell, &rule), Some([0.0, 0.5, 1.0, 1.0]));
+        assert_eq!(RenderMode::Age.colour(&cell.dead_clone(), &rule), None);
+    }
+
+    #[test]
+    fn ghost_mode_fades_recently_dead_cells() {
+        let rule = Rule::conway();
+        let never_alive = Cell::new(CellState::Dead, 0.0, 0.5, 1.0);
+        assert_eq!(RenderMode::Ghost.colour(&never_alive, &rule), None);
+
+        let mut cell = never_alive.alive_clone().dead_clone();
+        assert_eq!(cell.dead_for, 1);
+        let mut alphas = vec![];
+        while let Some([_, _, _, alpha]) = RenderMode::Ghost.colour(&cell, &rule) {
+            alphas.push(alpha);
+            cell = cell.dead_clone();
+        }
+        assert_eq!(alphas.len() as u32, GHOST_TRAIL_GENERATIONS - 1);
+        assert!(alphas.windows(2).all(|pair| pair[1] < pair[0]));
+        assert_eq!(RenderMode::Colour.colour(&never_alive.alive_clone().dead_clone(), &rule), None);
+        assert_eq!("Ghost".parse::<RenderMode>(), Ok(RenderMode::Ghost));
+    }
+
+    #[test]
+    fn wireworld_cells_have_fixed_colours() {
+        let rule = Rule::wireworld();
+        let conductor = Cell::new(CellState::Conductor, 0.0, 0.5, 1.0);
+        for mode in ALL {
+            assert_eq!(mode.colour(&conductor, &rule), Some([1.0, 0.5, 0.0, 1.0]));
+            assert_eq!(mode.colour(&conductor.next_clone(CellState::Head), &rule), Some([0.0, 0.5, 1.0, 1.0]));
+        }
+    }
+
+    #[test]
+    fn texels_cover_only_the_requested_cells() {
+        let rule = Rule::conway();
+        let alive = Cell::new(CellState::Dead, 0.2, 0.4, 1.0).alive_clone();
+        let dead = Cell::new(CellState::Dead, 0.2, 0.4, 1.0);
+        let cells = [((3, 5), &alive), ((4, 5), &dead), ((10, 5), &alive), ((2, 5), &alive)];
+        let texels = texels(cells, &rule, RenderMode::Colour, (3, 4), 2, 2);
+        // (3, 5) は左下のテクセルになり、死んだセルと範囲の外のセルは透明のまま
+        assert_eq!(texels, [0, 0, 0, 0, 0, 0, 0, 0, 51, 102, 255, 255, 0, 0, 0, 0]);
+    }
+}
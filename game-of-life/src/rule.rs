@@ -0,0 +1,438 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::neighbourhood::Neighbourhood;
+use crate::CellState;
+
+// Life-like（B/S表記）、Generations（B/S/C表記）、Larger than Life（R/C/M/S/B/N表記）のルールを表現するための構造体
+// 生存セルがn個接している死んだセルは birth が n を含むとき誕生し、
+// 生きたセルは survival が n を含むとき生存する
+// states が3以上の場合、生存できなかったセルは states - 2 世代かけて死滅していく
+// Wireworldのルールでは、導体は接している電子の頭の数が birth に含まれるとき電子の頭になる
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rule {
+    pub birth: Counts,
+    pub survival: Counts,
+    pub states: u8,                    // 生・死を含めたセルの状態の数（Life-likeなら2）
+    pub neighbourhood: Neighbourhood, // 接触数を数える近傍
+    pub family: Family,               // 次の状態の決め方
+}
+
+// 次の状態の決め方によるルールの種類
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Family {
+    #[default]
+    LifeLike,  // 生存セルの接触数で誕生・生存が決まる（Life-like・Generations・Larger than Life）
+    Wireworld, // 空・電子の頭・電子の尾・導体の4状態で、セルの状態ごとに決まった規則で変わる
+}
+
+// 誕生・生存の条件となる接触数の集合
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Counts {
+    List([bool; 9]), // 0〜8の個別の接触数（"B36" など）
+    Range(u32, u32), // min以上max以下の接触数（"B34..45" など）
+}
+
+impl Counts {
+    // 接触数nを含んでいるかを返すメソッド
+    pub fn contains(&self, n: u32) -> bool {
+        match self {
+            Counts::List(flags) => flags.get(n as usize).copied().unwrap_or(false),
+            Counts::Range(min, max) => *min <= n && n <= *max,
+        }
+    }
+}
+
+// 起動時やキー操作で選べる代表的なルール
+pub const PRESETS: [(&str, &str); 9] = [
+    ("Conway's Life", "B3/S23"),
+    ("HighLife", "B36/S23"),
+    ("Seeds", "B2/S"),
+    ("Day & Night", "B3678/S34678"),
+    ("Brian's Brain", "B2/S/C3"),
+    ("Star Wars", "B2/S345/C4"),
+    ("Bosco's Rule", "R5,C0,M1,S34..58,B34..45,NM"),
+    ("Hexagonal B2/S34", "B2/S34H"),
+    ("von Neumann B1/S1", "B1/S1V"),
+];
+
+impl Rule {
+    // 誕生・生存する接触数のリストからルールを生成するためのメソッド
+    pub fn new(birth: &[u32], survival: &[u32]) -> Rule {
+        let list = |counts: &[u32]| {
+            let mut flags = [false; 9];
+            for &n in counts {
+                flags[n as usize] = true;
+            }
+            Counts::List(flags)
+        };
+        Rule {
+            birth: list(birth),
+            survival: list(survival),
+            states: 2,
+            neighbourhood: Neighbourhood::default(),
+            family: Family::LifeLike,
+        }
+    }
+
+    // コンウェイのライフゲーム（B3/S23）
+    pub fn conway() -> Rule {
+        Rule::new(&[3], &[2, 3])
+    }
+
+    // Wireworld。電子の頭は尾に、尾は導体になり、導体は電子の頭が1つか2つ接していれば電子の頭になる
+    pub fn wireworld() -> Rule {
+        Rule { states: 4, family: Family::Wireworld, ..Rule::new(&[1, 2], &[]) }
+    }
+
+    // PRESETSのi番目のルールを返すメソッド
+    pub fn preset(i: usize) -> Option<Rule> {
+        PRESETS.get(i).map(|(_, rulestring)| rulestring.parse().unwrap())
+    }
+
+    // 現在の状態と接している生存セル（Wireworldでは電子の頭）の数から、次の世代の状態を返すメソッド
+    // ルールの種類に無い状態のセルは、死んだセルとして扱う
+    pub fn next_state(&self, state: CellState, neighbour_count: u32) -> CellState {
+        if self.family == Family::Wireworld {
+            return match state {
+                CellState::Head => CellState::Tail,
+                CellState::Tail => CellState::Conductor,
+                CellState::Conductor if self.birth.contains(neighbour_count) => CellState::Head,
+                CellState::Conductor => CellState::Conductor,
+                _ => CellState::Dead,
+            };
+        }
+        match state {
+            CellState::Alive if self.survival.contains(neighbour_count) => CellState::Alive,
+            CellState::Alive => self.dying_after(0),
+            CellState::Dying(age) => self.dying_after(age),
+            _ if self.birth.contains(neighbour_count) => CellState::Alive,
+            _ => CellState::Dead,
+        }
+    }
+
+    // 接触数を数えるときに数える状態かどうか（Life-likeでは生存、Wireworldでは電子の頭）
+    pub fn counts(&self, state: CellState) -> bool {
+        match self.family {
+            Family::LifeLike => state == CellState::Alive,
+            Family::Wireworld => state == CellState::Head,
+        }
+    }
+
+    // 多状態のRLEなどで使う状態番号（0が死）から状態を返すメソッド
+    // Generationsでは1が生存、2以降が死につつある状態で、Wireworldでは1〜3が電子の頭・尾・導体
+    // ルールの状態の数を超える番号は生存（Wireworldでは導体）として扱う
+    pub fn state(&self, index: u8) -> CellState {
+        match (self.family, index) {
+            (_, 0) => CellState::Dead,
+            (Family::Wireworld, 1) => CellState::Head,
+            (Family::Wireworld, 2) => CellState::Tail,
+            (Family::Wireworld, _) => CellState::Conductor,
+            (Family::LifeLike, index) if 1 < index && index < self.states => CellState::Dying(index - 1),
+            (Family::LifeLike, _) => CellState::Alive,
+        }
+    }
+
+    // 状態の状態番号を返すメソッド（stateの逆）
+    pub fn index(&self, state: CellState) -> u8 {
+        match state {
+            CellState::Dead => 0,
+            CellState::Alive | CellState::Head => 1,
+            CellState::Dying(age) => age + 1,
+            CellState::Tail => 2,
+            CellState::Conductor => 3,
+        }
+    }
+
+    // 死につつある状態をひとつ進める。statesを使い切ったら死滅する
+    fn dying_after(&self, age: u8) -> CellState {
+        if age + 2 < self.states {
+            CellState::Dying(age + 1)
+        } else {
+            CellState::Dead
+        }
+    }
+
+    // 描画のための状態ごとの明るさ（生存が1.0、死滅が0.0で、死につつある状態はその間で薄れていく）
+    pub fn intensity(&self, state: CellState) -> f32 {
+        match state {
+            CellState::Dying(age) => 1.0 - age as f32 / (self.states - 1) as f32,
+            CellState::Dead => 0.0,
+            _ => 1.0,
+        }
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Rule {
+        Rule::conway()
+    }
+}
+
+// "B3/S23" 形式のルール文字列を出力する
+// 範囲で指定されたルールは "R5,C0,M1,S34..58,B34..45,NM" 形式で出力する
+// WireworldはGollyと同じく "WireWorld" と出力する
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.family == Family::Wireworld {
+            return write!(f, "WireWorld");
+        }
+        if let (Counts::Range(b_min, b_max), Counts::Range(s_min, s_max)) = (self.birth, self.survival) {
+            // 生きたセルは自分自身も数える（M1）形式で書き出す
+            let (shape, radius) = match self.neighbourhood {
+                Neighbourhood::VonNeumann { radius } => ('N', radius),
+                neighbourhood => ('M', neighbourhood.radius()),
+            };
+            let states = if self.states > 2 { self.states } else { 0 };
+            return write!(f, "R{},C{},M1,S{}..{},B{}..{},N{}",
+                radius, states, s_min + 1, s_max + 1, b_min, b_max, shape);
+        }
+
+        let digits = |counts: &Counts| -> String {
+            (0..9).filter(|&n| counts.contains(n)).map(|n| n.to_string()).collect()
+        };
+        write!(f, "B{}/S{}", digits(&self.birth), digits(&self.survival))?;
+        if self.states > 2 {
+            write!(f, "/C{}", self.states)?;
+        }
+        match self.neighbourhood {
+            Neighbourhood::Hexagonal => write!(f, "H"),
+            Neighbourhood::VonNeumann { .. } => write!(f, "V"),
+            Neighbourhood::Moore { .. } => Ok(()),
+        }
+    }
+}
+
+// "B3/S23" 形式（大文字小文字は問わない）と、従来の "23/3"（S/B）形式のルール文字列を読み込む
+// Generationsルールは "B2/S/C3" や "345/2/4"（S/B/C）のように3つ目に状態数を書く
+// 末尾に H を付けると六角形、V を付けるとvon Neumann近傍になる
+// "R" で始まる文字列はLarger than Lifeのルールとして、"WireWorld"（大文字小文字は問わない）はWireworldとして読み込む
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Rule, String> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("wireworld") {
+            return Ok(Rule::wireworld());
+        }
+        if s.starts_with(['R', 'r']) {
+            return parse_larger_than_life(s);
+        }
+
+        let (body, neighbourhood) = match s.chars().last().map(|c| c.to_ascii_uppercase()) {
+            Some('H') => (&s[..s.len() - 1], Neighbourhood::Hexagonal),
+            Some('V') => (&s[..s.len() - 1], Neighbourhood::VonNeumann { radius: 1 }),
+            _ => (s, Neighbourhood::default()),
+        };
+        let mut parts: Vec<&str> = body.split('/').collect();
+        let states = match parts.len() {
+            2 => 2,
+            3 => parse_states(parts.pop().unwrap(), s)?,
+            _ => return Err(format!("invalid rulestring {:?}: expected two or three parts separated by '/'", s)),
+        };
+
+        let mut birth = None;
+        let mut survival = None;
+        for part in &parts {
+            let mut chars = part.chars();
+            match chars.next().map(|c| c.to_ascii_uppercase()) {
+                Some('B') => birth = Some(parse_digits(chars.as_str(), s)?),
+                Some('S') => survival = Some(parse_digits(chars.as_str(), s)?),
+                _ => {},
+            }
+        }
+
+        let (birth, survival) = match (birth, survival) {
+            (Some(birth), Some(survival)) => (birth, survival),
+            // 接頭辞が無い場合は S/B 表記とみなす
+            (None, None) => (parse_digits(parts[1], s)?, parse_digits(parts[0], s)?),
+            _ => return Err(format!("invalid rulestring {:?}: needs both B and S parts", s)),
+        };
+        if let Some(&n) = birth.iter().chain(&survival).find(|&&n| neighbourhood.size() < n) {
+            return Err(format!("invalid rulestring {:?}: {} neighbours is impossible in this neighbourhood", s, n));
+        }
+        let mut rule = Rule::new(&birth, &survival);
+        rule.states = states;
+        rule.neighbourhood = neighbourhood;
+        Ok(rule)
+    }
+}
+
+// "C3" や "3" のような状態数の指定を読み込む
+fn parse_states(part: &str, rulestring: &str) -> Result<u8, String> {
+    let digits = part.trim_start_matches(['C', 'c', 'G', 'g']);
+    match digits.parse::<u8>() {
+        Ok(states) if states >= 2 => Ok(states),
+        _ => Err(format!("invalid rulestring {:?}: number of states must be 2 to 255", rulestring)),
+    }
+}
+
+// "236" のような数字の並びを接触数のリストに変換する
+fn parse_digits(digits: &str, rulestring: &str) -> Result<Vec<u32>, String> {
+    digits.chars().map(|c| {
+        match c.to_digit(10) {
+            Some(n) if n <= 8 => Ok(n),
+            _ => Err(format!("invalid rulestring {:?}: unexpected character {:?}", rulestring, c)),
+        }
+    }).collect()
+}
+
+// "R5,C0,M1,S34..58,B34..45,NM" 形式のLarger than Lifeのルールを読み込む
+// R: 半径、C: 状態数（0と2はLife-like）、M: 自分自身を数えるか、S/B: 生存・誕生する接触数の範囲、N: 近傍の形（M: 正方形、N: ひし形）
+fn parse_larger_than_life(s: &str) -> Result<Rule, String> {
+    let invalid = |reason: &str| format!("invalid rulestring {:?}: {}", s, reason);
+    let number = |value: &str| value.parse::<u32>().map_err(|_| invalid(&format!("{:?} is not a number", value)));
+    let range = |value: &str| -> Result<(u32, u32), String> {
+        let (min, max) = value.split_once("..").ok_or_else(|| invalid("ranges must look like S34..58"))?;
+        Ok((number(min)?, number(max)?))
+    };
+
+    let (mut radius, mut states, mut middle, mut shape) = (None, 2, false, 'M');
+    let (mut birth, mut survival) = (None, None);
+    for item in s.split(',') {
+        let item = item.trim();
+        let (key, value) = item.split_at(item.chars().next().map_or(0, |c| c.len_utf8()));
+        match key.to_ascii_uppercase().as_str() {
+            "R" => radius = Some(number(value)?),
+            "C" => states = number(value)?.max(2),
+            "M" => middle = number(value)? == 1,
+            "S" => survival = Some(range(value)?),
+            "B" => birth = Some(range(value)?),
+            "N" => shape = value.chars().next().map_or('M', |c| c.to_ascii_uppercase()),
+            _ => return Err(invalid(&format!("unknown item {:?}", item))),
+        }
+    }
+
+    let radius = radius.ok_or_else(|| invalid("missing R"))?;
+    if radius == 0 || states > 255 {
+        return Err(invalid("radius must be at least 1 and C at most 255"));
+    }
+    let neighbourhood = match shape {
+        'M' => Neighbourhood::Moore { radius },
+        'N' => Neighbourhood::VonNeumann { radius },
+        _ => return Err(invalid("N must be M or N")),
+    };
+    let (b_min, b_max) = birth.ok_or_else(|| invalid("missing B"))?;
+    let (mut s_min, mut s_max) = survival.ok_or_else(|| invalid("missing S"))?;
+    if middle {
+        // 自分自身を数える場合、生きたセルの接触数は近傍の数より1多い
+        s_min = s_min.saturating_sub(1);
+        s_max = s_max.saturating_sub(1);
+    }
+    Ok(Rule {
+        birth: Counts::Range(b_min, b_max),
+        survival: Counts::Range(s_min, s_max),
+        states: states as u8,
+        neighbourhood,
+        family: Family::LifeLike,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_presets() {
+        let conway: Rule = "B3/S23".parse().unwrap();
+        assert_eq!(conway, Rule::conway());
+        assert_eq!(Rule::preset(1).unwrap(), Rule::new(&[3, 6], &[2, 3]));
+        assert_eq!(Rule::preset(2).unwrap(), Rule::new(&[2], &[]));
+        assert_eq!(Rule::preset(3).unwrap(), Rule::new(&[3, 6, 7, 8], &[3, 4, 6, 7, 8]));
+        assert_eq!(Rule::preset(4).unwrap().states, 3);
+        assert_eq!(Rule::preset(5).unwrap().states, 4);
+        assert_eq!(Rule::preset(6).unwrap().neighbourhood, Neighbourhood::Moore { radius: 5 });
+        assert_eq!(Rule::preset(7).unwrap().neighbourhood, Neighbourhood::Hexagonal);
+        assert_eq!(Rule::preset(8).unwrap().neighbourhood, Neighbourhood::VonNeumann { radius: 1 });
+        assert_eq!(Rule::preset(9), None);
+    }
+
+    #[test]
+    fn parses_alternative_notations() {
+        assert_eq!("b36/s23".parse::<Rule>().unwrap(), Rule::preset(1).unwrap());
+        assert_eq!("S23/B3".parse::<Rule>().unwrap(), Rule::conway());
+        assert_eq!("23/3".parse::<Rule>().unwrap(), Rule::conway());
+        assert_eq!("/2".parse::<Rule>().unwrap(), Rule::preset(2).unwrap());
+        assert_eq!("/2/3".parse::<Rule>().unwrap(), Rule::preset(4).unwrap());
+        assert_eq!("345/2/4".parse::<Rule>().unwrap(), Rule::preset(5).unwrap());
+        assert_eq!("B3/S23/C2".parse::<Rule>().unwrap(), Rule::conway());
+        assert_eq!("34/2h".parse::<Rule>().unwrap(), Rule::preset(7).unwrap());
+    }
+
+    #[test]
+    fn parses_larger_than_life() {
+        let bosco = Rule::preset(6).unwrap();
+        assert_eq!(bosco.birth, Counts::Range(34, 45));
+        // M1なので生存の範囲は自分自身を除いた数に直して保持する
+        assert_eq!(bosco.survival, Counts::Range(33, 57));
+        assert_eq!(bosco.states, 2);
+        assert_eq!("R5,C0,M0,S33..57,B34..45,NM".parse::<Rule>().unwrap(), bosco);
+
+        let diamond: Rule = "R3,C4,M0,S2..4,B3..3,NN".parse().unwrap();
+        assert_eq!(diamond.neighbourhood, Neighbourhood::VonNeumann { radius: 3 });
+        assert_eq!(diamond.states, 4);
+        assert_eq!(diamond.next_state(CellState::Dead, 3), CellState::Alive);
+        assert_eq!(diamond.next_state(CellState::Alive, 5), CellState::Dying(1));
+    }
+
+    #[test]
+    fn rejects_invalid_rulestrings() {
+        assert!("B3S23".parse::<Rule>().is_err());
+        assert!("B9/S23".parse::<Rule>().is_err());
+        assert!("B3/X23".parse::<Rule>().is_err());
+        assert!("B3/S23/C1".parse::<Rule>().is_err());
+        assert!("B3/S23/CX".parse::<Rule>().is_err());
+        assert!("B3/S2/3/4".parse::<Rule>().is_err());
+        assert!("B27/S34H".parse::<Rule>().is_err());
+        assert!("B5/S1V".parse::<Rule>().is_err());
+        assert!("R0,C0,M0,S1..2,B3..3,NM".parse::<Rule>().is_err());
+        assert!("R2,C0,M0,S1..2,NM".parse::<Rule>().is_err());
+        assert!("R2,C0,M0,S1-2,B3..3,NM".parse::<Rule>().is_err());
+        assert!("R2,C0,M0,S1..2,B3..3,NX".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn dying_cells_count_down_through_the_states() {
+        let star_wars = Rule::preset(5).unwrap();
+        assert_eq!(star_wars.next_state(CellState::Alive, 4), CellState::Alive);
+        assert_eq!(star_wars.next_state(CellState::Alive, 2), CellState::Dying(1));
+        assert_eq!(star_wars.next_state(CellState::Dying(1), 2), CellState::Dying(2));
+        assert_eq!(star_wars.next_state(CellState::Dying(2), 2), CellState::Dead);
+        assert_eq!(star_wars.next_state(CellState::Dead, 2), CellState::Alive);
+
+        let conway = Rule::conway();
+        assert_eq!(conway.next_state(CellState::Alive, 4), CellState::Dead);
+        assert_eq!(conway.intensity(CellState::Alive), 1.0);
+        assert!((star_wars.intensity(CellState::Dying(2)) - 1.0 / 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn wireworld_depends_on_the_state() {
+        let wireworld: Rule = "wireworld".parse().unwrap();
+        assert_eq!(wireworld, Rule::wireworld());
+        assert_eq!(wireworld.to_string(), "WireWorld");
+        assert_eq!(wireworld.next_state(CellState::Head, 0), CellState::Tail);
+        assert_eq!(wireworld.next_state(CellState::Tail, 5), CellState::Conductor);
+        assert_eq!(wireworld.next_state(CellState::Conductor, 0), CellState::Conductor);
+        assert_eq!(wireworld.next_state(CellState::Conductor, 1), CellState::Head);
+        assert_eq!(wireworld.next_state(CellState::Conductor, 2), CellState::Head);
+        assert_eq!(wireworld.next_state(CellState::Conductor, 3), CellState::Conductor);
+        assert_eq!(wireworld.next_state(CellState::Dead, 2), CellState::Dead);
+        assert!(wireworld.counts(CellState::Head) && !wireworld.counts(CellState::Alive));
+
+        // 状態番号はGollyと同じ
+        for (index, state) in [(0, CellState::Dead), (1, CellState::Head), (2, CellState::Tail), (3, CellState::Conductor)] {
+            assert_eq!((wireworld.state(index), wireworld.index(state)), (state, index));
+        }
+        let star_wars = Rule::preset(5).unwrap();
+        assert_eq!((star_wars.state(3), star_wars.index(CellState::Dying(2))), (CellState::Dying(2), 3));
+        assert_eq!(Rule::conway().state(7), CellState::Alive);
+    }
+
+    #[test]
+    fn displays_as_rulestring() {
+        for (_, rulestring) in PRESETS {
+            assert_eq!(rulestring.parse::<Rule>().unwrap().to_string(), rulestring);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::manifest::Manifest;
+use crate::render::RenderMode;
+use crate::{Cell, CellState, Field};
+
+// セッションファイルの形式のバージョン。形式を変えたら1つ増やし、古い形式を読み込めるようにする
+pub const VERSION: u32 = 1;
+
+// セッションファイルの先頭に書くコメント
+const HEADER: &str = "# game-of-life session";
+
+// マニフェストと共通のキー
+const MANIFEST_KEYS: [&str; 7] = ["seed", "rule", "boundary", "size", "alive_ratio", "generation", "backend"];
+
+// 実験を中断し、後で同じ状態から再開するために保存する情報をまとめた構造体
+// マニフェストと違い、手で編集したセルも含めてすべてのセルの状態と色をそのまま保存する
+pub struct Session {
+    pub field: Field,             // すべてのセルとルール・境界・色の決め方
+    pub generation: u64,          // 生成してから進めた世代数
+    pub seed: u64,                // フィールドを生成した乱数の種
+    pub alive_ratio: f32,         // フィールド生成時に生存しているセルの割合
+    pub edited: bool,             // 生成してから手でセルを編集したかどうか
+    pub rng: ChaCha8Rng,          // 次に生成し直すときの乱数の種を選ぶ乱数生成器
+    pub paused: bool,             // 一時停止しているかどうか
+    pub iteration_interval: f32,  // フィールドの更新間隔（秒）
+    pub auto_reseed: Option<u64>, // Someのときは、落ち着いてからこの世代数が経つと生成し直す
+    pub render_mode: RenderMode,  // セルの描き方
+}
+
+impl Session {
+    // フィールドを生成した情報を、マニフェストとして返すメソッド
+    pub fn manifest(&self) -> Manifest {
+        Manifest {
+            seed: self.seed,
+            rule: self.field.rule,
+            boundary: self.field.boundary,
+            col_n: self.field.col_n,
+            row_n: self.field.row_n,
+            alive_ratio: self.alive_ratio,
+            generation: self.generation,
+            // セッションはすべてのセルをそのまま保存するため、計算方法は記録しない
+            backend: Default::default(),
+        }
+    }
+}
+
+// "key = value" の行を並べたあとに "cells" の行を書き、その後に1行1セルずつ x * row_n + y の順でセルを書き出す
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "version = {}", VERSION)?;
+        write!(f, "{}", self.manifest())?;
+        writeln!(f, "colours = {}", self.field.colour_mode)?;
+        writeln!(f, "edited = {}", self.edited)?;
+        let seed: String = self.rng.get_seed().iter().map(|byte| format!("{:02x}", byte)).collect();
+        writeln!(f, "rng = {} {} {}", seed, self.rng.get_stream(), self.rng.get_word_pos())?;
+        writeln!(f, "paused = {}", self.paused)?;
+        writeln!(f, "interval = {}", self.iteration_interval)?;
+        match self.auto_reseed {
+            Some(grace) => writeln!(f, "auto_reseed = {}", grace)?,
+            None => writeln!(f, "auto_reseed = off")?,
+        }
+        writeln!(f, "render = {}", self.render_mode)?;
+        writeln!(f, "cells")?;
+        // 色はf32の値をそのまま書き、読み込んだときに同じ値に戻るようにする
+        for cell in self.field.matrix.iter().flatten() {
+            let state = match cell.state {
+                CellState::Alive => "alive".to_string(),
+                CellState::Dying(age) => format!("dying:{}", age),
+                CellState::Dead => "dead".to_string(),
+                CellState::Head => "head".to_string(),
+                CellState::Tail => "tail".to_string(),
+                CellState::Conductor => "conductor".to_string(),
+            };
+            let [r, g, b] = cell.rgb;
+            writeln!(f, "{} {:?} {:?} {:?} {} {}", state, r, g, b, cell.age, cell.dead_for)?;
+        }
+        Ok(())
+    }
+}
+
+// Displayで書き出した形式を読み込む。#で始まる行と空行は無視する
+// このプログラムより新しいバージョンで書かれたファイルは、バージョンを含めたエラーにする
+impl FromStr for Session {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Session, String> {
+        let mut lines = s.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        // "cells" の行までが "key = value" の行
+        let mut entries = vec![];
+        for line in lines.by_ref() {
+            if line == "cells" {
+                break;
+            }
+            let (key, value) = line.split_once('=')
+                .ok_or_else(|| format!("invalid session line {:?}", line))?;
+            entries.push((key.trim(), value.trim()));
+        }
+        let value = |key: &str| entries.iter().find(|(k, _)| *k == key).map(|&(_, value)| value);
+        let version = value("version").ok_or("not a game-of-life session file: it has no version line")?;
+        match version.parse::<u32>() {
+            Ok(VERSION) => {},
+            Ok(version) if VERSION < version => {
+                return Err(format!("the session file is version {}, but this program only reads up to version {}; please update it", version, VERSION));
+            },
+            _ => return Err(format!("unsupported session file version {:?}", version)),
+        }
+        let required = |key: &str| value(key).ok_or_else(|| format!("session is missing {}", key));
+        let invalid = |key: &str, err: &dyn fmt::Display| format!("invalid session {} {:?}: {}", key, value(key).unwrap_or(""), err);
+        if let Some(&(key, _)) = entries.iter().find(|(key, _)| {
+            !MANIFEST_KEYS.contains(key)
+                && !["version", "colours", "edited", "rng", "paused", "interval", "auto_reseed", "render"].contains(key)
+        }) {
+            return Err(format!("unknown session key {:?}", key));
+        }
+
+        // マニフェストと共通の部分は、マニフェストとして読み込む
+        let manifest: Manifest = entries.iter()
+            .filter(|(key, _)| MANIFEST_KEYS.contains(key))
+            .map(|(key, value)| format!("{} = {}\n", key, value))
+            .collect::<String>()
+            .parse()?;
+        let colour_mode = required("colours")?.parse()?;
+
+        let mut rng_parts = required("rng")?.split_whitespace();
+        let rng_seed = rng_parts.next().unwrap_or("");
+        if rng_seed.len() != 64 || !rng_seed.is_ascii() {
+            return Err(invalid("rng", &"expected a 64-digit hexadecimal seed"));
+        }
+        let mut seed = [0u8; 32];
+        for (i, byte) in seed.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&rng_seed[i * 2..i * 2 + 2], 16).map_err(|err| invalid("rng", &err))?;
+        }
+        let mut rng = ChaCha8Rng::from_seed(seed);
+        rng.set_stream(rng_parts.next().unwrap_or("").parse().map_err(|err| invalid("rng", &err))?);
+        rng.set_word_pos(rng_parts.next().unwrap_or("").parse().map_err(|err| invalid("rng", &err))?);
+
+        // 壊れたファイルの大きさでフィールドを確保しないよう、セルの行数が大きさと合うことを確かめてから確保する
+        let cell_lines: Vec<&str> = lines.collect();
+        let total = (manifest.col_n as usize).checked_mul(manifest.row_n as usize).ok_or("session size is too large")?;
+        if cell_lines.len() != total {
+            return Err(format!("session has {} cells, but its size needs {}", cell_lines.len(), total));
+        }
+        let mut field = Field::new(manifest.row_n, manifest.col_n, 0.0);
+        field.rule = manifest.rule;
+        field.boundary = manifest.boundary;
+        field.colour_mode = colour_mode;
+        for (cell, line) in field.matrix.iter_mut().flatten().zip(cell_lines) {
+            *cell = parse_cell(line)?;
+        }
+
+        Ok(Session {
+            field,
+            generation: manifest.generation,
+            seed: manifest.seed,
+            alive_ratio: manifest.alive_ratio,
+            edited: required("edited")?.parse().map_err(|err| invalid("edited", &err))?,
+            rng,
+            paused: required("paused")?.parse().map_err(|err| invalid("paused", &err))?,
+            iteration_interval: required("interval")?.parse().map_err(|err| invalid("interval", &err))?,
+            auto_reseed: match required("auto_reseed")? {
+                "off" => None,
+                grace => Some(grace.parse().map_err(|err| invalid("auto_reseed", &err))?),
+            },
+            render_mode: required("render")?.parse()?,
+        })
+    }
+}
+
+// "<状態> <赤> <緑> <青> <年齢> <死んでからの世代数>" 形式の1行からセルを読み込む
+fn parse_cell(line: &str) -> Result<Cell, String> {
+    let invalid = |err: &dyn fmt::Display| format!("invalid session cell {:?}: {}", line, err);
+    let items: Vec<&str> = line.split_whitespace().collect();
+    let [state, r, g, b, age, dead_for] = items[..] else {
+        return Err(invalid(&"expected <state> <r> <g> <b> <age> <dead_for>"));
+    };
+    let state = match state {
+        "alive" => CellState::Alive,
+        "dead" => CellState::Dead,
+        "head" => CellState::Head,
+        "tail" => CellState::Tail,
+        "conductor" => CellState::Conductor,
+        state => match state.strip_prefix("dying:").map(|age| age.parse::<u8>()) {
+            Some(Ok(age)) => CellState::Dying(age),
+            _ => return Err(invalid(&"the state must be alive, dead, dying:<n>, head, tail or conductor")),
+        },
+    };
+    let channel = |value: &str| value.parse::<f32>().map_err(|err| invalid(&err));
+    Ok(Cell {
+        state,
+        rgb: [channel(r)?, channel(g)?, channel(b)?],
+        age: age.parse().map_err(|err| invalid(&err))?,
+        dead_for: dead_for.parse().map_err(|err| invalid(&err))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary::Boundary;
+    use crate::colour::ColourMode;
+    use rand::Rng;
+
+    fn session() -> Session {
+        let mut field = Field::with_seed(12, 20, 0.4, 5);
+        field.rule = "B2/S/C3".parse().unwrap();
+        field.boundary = Boundary::Torus;
+        field.colour_mode = ColourMode::Blend;
+        for _ in 0..7 {
+            field.iterate();
+        }
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        rng.gen::<u64>();
+        Session {
+            field,
+            generation: 7,
+            seed: 5,
+            alive_ratio: 0.4,
+            edited: true,
+            rng,
+            paused: true,
+            iteration_interval: 0.025,
+            auto_reseed: Some(50),
+            render_mode: RenderMode::Ghost,
+        }
+    }
+
+    fn cells(field: &Field) -> Vec<(CellState, [f32; 3], u32, u32)> {
+        field.matrix.iter().flatten().map(|cell| (cell.state, cell.rgb, cell.age, cell.dead_for)).collect()
+    }
+
+    #[test]
+    fn round_trips_the_whole_state() {
+        let saved = session();
+        let mut loaded: Session = saved.to_string().parse().unwrap();
+        assert_eq!(cells(&loaded.field), cells(&saved.field));
+        assert_eq!(loaded.manifest(), saved.manifest());
+        assert_eq!(loaded.field.colour_mode, ColourMode::Blend);
+        assert_eq!((loaded.edited, loaded.paused, loaded.auto_reseed), (true, true, Some(50)));
+        assert_eq!((loaded.iteration_interval, loaded.render_mode), (0.025, RenderMode::Ghost));
+
+        // 乱数生成器は、保存した時点の続きから同じ値を返す
+        let mut rng = saved.rng.clone();
+        assert_eq!(loaded.rng.gen::<u64>(), rng.gen::<u64>());
+
+        // 読み込んだフィールドは保存したフィールドと同じように進む
+        let mut original = saved.field;
+        original.iterate();
+        loaded.field.iterate();
+        assert_eq!(cells(&loaded.field), cells(&original));
+    }
+
+    #[test]
+    fn rejects_other_versions_with_a_clear_error() {
+        let text = session().to_string();
+        let newer = text.replace("version = 1", "version = 2");
+        assert!(newer.parse::<Session>().err().unwrap().contains("only reads up to version 1"));
+        let manifest_only = "seed = 1\nrule = B3/S23\nsize = 10x10\nalive_ratio = 0.5\n";
+        assert!(manifest_only.parse::<Session>().err().unwrap().contains("no version line"));
+    }
+
+    #[test]
+    fn rejects_truncated_sessions() {
+        let text = session().to_string();
+        let truncated = &text[..text.trim_end().rfind('\n').unwrap()];
+        assert!(truncated.parse::<Session>().err().unwrap().contains("needs 240"));
+        assert!(text.replace("\nalive ", "\nzombie ").parse::<Session>().is_err());
+        assert!((text.clone() + "dead 0.0 0.0 0.0 0 0\n").parse::<Session>().is_err());
+        // 大きさが壊れていても、フィールドを確保する前にエラーにする
+        let huge = text.replace("size = 20x12", "size = 100000x100000");
+        assert!(huge.parse::<Session>().err().unwrap().contains("needs 10000000000"));
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+
+use crate::colour::ColourMode;
+use crate::rule::{Family, Rule};
+use crate::{Cell, CellState, Field};
+
+// 生きている（または死につつある）セルと、残像を描く間の死んだセルだけを座標をキーにして持つ、端のないフィールド
+// パターンがどこまで広がっても、セルの数に比例した時間で次の世代を計算できる
+pub struct SparseField {
+    pub cells: HashMap<(i64, i64), Cell>, // 死んでいないセルと、残像を残している死んだセル。キーは座標(x, y)
+    pub rule: Rule,                       // 誕生・生存の条件となるルール
+    pub colour_mode: ColourMode,          // 新しく生まれたセルの色の決め方
+    pub trail_generations: u32,           // 死んだセルを残しておく世代数（Ghostの残像を描くため。0なら死んだらすぐに捨てる）
+}
+
+impl SparseField {
+    // 端のないフィールドで扱えるルールかどうか
+    // 接触数0で誕生するルールでは無限に広い平面のすべてが生きてしまうため扱えない
+    // Wireworldの導体は生きているセルの近くになくても残り続けるため扱えない
+    pub fn supports(rule: &Rule) -> bool {
+        !rule.birth.contains(0) && rule.family == Family::LifeLike
+    }
+
+    // 空のフィールドを作るためのメソッド
+    pub fn new(rule: Rule) -> SparseField {
+        SparseField { cells: HashMap::new(), rule, colour_mode: ColourMode::default(), trail_generations: 0 }
+    }
+
+    // 通常のフィールドの死んでいないセルを、同じ座標と色のまま写し取るためのメソッド
+    pub fn from_field(field: &Field) -> SparseField {
+        let mut sparse = SparseField::new(field.rule);
+        sparse.colour_mode = field.colour_mode;
+        for (x, col) in field.matrix.iter().enumerate() {
+            for (y, cell) in col.iter().enumerate() {
+                if cell.state != CellState::Dead {
+                    sparse.cells.insert((x as i64, y as i64), *cell);
+                }
+            }
+        }
+        sparse
+    }
+
+    // フィールドの範囲内にあるセルを、通常のフィールドに書き戻すためのメソッド
+    pub fn write_to(&self, field: &mut Field) {
+        field.clear();
+        for (&(x, y), cell) in &self.cells {
+            if 0 <= x && x < field.col_n as i64 && 0 <= y && y < field.row_n as i64 {
+                field.matrix[x as usize][y as usize] = *cell;
+            }
+        }
+    }
+
+    // (x, y)のセルを生きている状態、または死んだ状態にするためのメソッド
+    pub fn set(&mut self, position: (i64, i64), alive: bool) {
+        if alive {
+            let cell = self.cells.get(&position).copied().unwrap_or_else(|| Cell::positional(position));
+            self.cells.insert(position, cell.alive_clone());
+        } else {
+            self.cells.remove(&position);
+        }
+    }
+
+    // 生きているセルの数
+    pub fn population(&self) -> u64 {
+        self.cells.values().filter(|cell| cell.state == CellState::Alive).count() as u64
+    }
+
+    // フィールドを次の状態に移行するためのメソッド
+    pub fn iterate(&mut self) {
+        // 近傍のセルの相対位置は、行の偶奇ごとに一度だけ求めておく
+        let offsets = [self.rule.neighbourhood.offsets(0), self.rule.neighbourhood.offsets(1)];
+        // 生きているセルから近傍のセルへ1ずつ配り、接触数を数える
+        let mut counts: HashMap<(i64, i64), u32> = HashMap::new();
+        for (&(x, y), cell) in &self.cells {
+            if cell.state != CellState::Alive {
+                continue;
+            }
+            for &(offset_x, offset_y) in &offsets[y.rem_euclid(2) as usize] {
+                *counts.entry((x + offset_x as i64, y + offset_y as i64)).or_insert(0) += 1;
+            }
+        }
+
+        // 状態が変わりうるのは、死んでいないセルと生きたセルに接しているセルだけ
+        let mut next = HashMap::new();
+        for (&position, &count) in &counts {
+            let cell = self.cells.get(&position).copied().unwrap_or_else(|| Cell::positional(position));
+            let state = self.rule.next_state(cell.state, count);
+            if state == CellState::Alive && cell.state != CellState::Alive {
+                // 新しく生まれたセルは、色を受け継ぐモードであれば親の色になる
+                if let Some(rgb) = self.colour_mode.inherit(&self.parent_colours(position, &offsets)) {
+                    next.insert(position, cell.born_clone(rgb));
+                    continue;
+                }
+            }
+            let cell = cell.next_clone(state);
+            if self.keeps(&cell) {
+                next.insert(position, cell);
+            }
+        }
+        for (&position, &cell) in &self.cells {
+            if counts.contains_key(&position) {
+                continue;
+            }
+            let cell = cell.next_clone(self.rule.next_state(cell.state, 0));
+            if self.keeps(&cell) {
+                next.insert(position, cell);
+            }
+        }
+        self.cells = next;
+    }
+
+    // 次の世代にも残しておくセルかどうか。死んだセルは、残像を描く間だけ残す
+    fn keeps(&self, cell: &Cell) -> bool {
+        cell.state != CellState::Dead || cell.dead_for < self.trail_generations
+    }
+
+    // (x, y)に接している生存セル（新しく生まれるセルの親）の色を、近傍の並び順で返すメソッド
+    // offsetsは偶数行・奇数行それぞれの近傍の相対位置
+    fn parent_colours(&self, (x, y): (i64, i64), offsets: &[Vec<(i32, i32)>; 2]) -> Vec<[f32; 3]> {
+        if self.colour_mode == ColourMode::Positional {
+            return vec![];
+        }
+        offsets[y.rem_euclid(2) as usize].iter()
+            .filter_map(|&(offset_x, offset_y)| self.cells.get(&(x + offset_x as i64, y + offset_y as i64)))
+            .filter(|cell| cell.state == CellState::Alive)
+            .map(|cell| cell.rgb)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    fn alive_cells(sparse: &SparseField) -> Vec<(i64, i64)> {
+        let mut cells: Vec<(i64, i64)> = sparse.cells.iter()
+            .filter(|(_, cell)| cell.state == CellState::Alive)
+            .map(|(&position, _)| position)
+            .collect();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn glider_travels_past_any_edge() {
+        let mut sparse = SparseField::new(Rule::conway());
+        for position in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            sparse.cells.insert(position, Cell::positional(position).alive_clone());
+        }
+        let start = alive_cells(&sparse);
+        for _ in 0..4 * 1000 {
+            sparse.iterate();
+        }
+        // 4世代で右下に1セルずつ進む
+        let moved: Vec<(i64, i64)> = start.iter().map(|&(x, y)| (x + 1000, y + 1000)).collect();
+        assert_eq!(alive_cells(&sparse), moved);
+    }
+
+    #[test]
+    fn matches_the_dense_field_away_from_its_edges() {
+        // 光速（1世代に近傍の半径ぶん）で広がっても端に届かない世代数だけ進め、通常のフィールドと比べる
+        for rule in ["B3/S23", "B36/S23", "B2/S/C3", "B2/S34H", "B1/S1V", "R2,C0,M0,S3..5,B3..4,NM"] {
+            let rule: Rule = rule.parse().unwrap();
+            let mut field = Field::new(80, 80, 0.0);
+            field.rule = rule;
+            let mut rng = StdRng::seed_from_u64(8);
+            for x in 30..50 {
+                for y in 30..50 {
+                    if rng.gen_bool(0.4) {
+                        field.matrix[x][y].state = CellState::Alive;
+                    }
+                }
+            }
+            let mut sparse = SparseField::from_field(&field);
+            for _ in 0..28 / rule.neighbourhood.radius() {
+                field.iterate();
+                sparse.iterate();
+            }
+            for (x, col) in field.matrix.iter().enumerate() {
+                for (y, cell) in col.iter().enumerate() {
+                    let state = sparse.cells.get(&(x as i64, y as i64)).map_or(CellState::Dead, |cell| cell.state);
+                    assert_eq!(state, cell.state, "{} at ({}, {})", rule, x, y);
+                }
+            }
+            assert_eq!(sparse.population(), field.population());
+        }
+    }
+
+    #[test]
+    fn keeps_dead_cells_for_ghost_trails() {
+        // 縦のブリンカーの両端は、横になったときに死ぬ
+        let mut sparse = SparseField::new(Rule::conway());
+        for position in [(0, -1), (0, 0), (0, 1)] {
+            sparse.cells.insert(position, Cell::positional(position).alive_clone());
+        }
+        sparse.iterate();
+        assert!(!sparse.cells.contains_key(&(0, -1)));
+
+        // 残像を残すようにすると、死んだ両端も死んでからの世代数とともに残る
+        sparse.trail_generations = 3;
+        sparse.iterate();
+        let cell = |position| sparse.cells.get(&position).map(|cell: &Cell| (cell.state, cell.dead_for));
+        assert_eq!(cell((-1, 0)), Some((CellState::Dead, 1)));
+        assert_eq!(cell((0, -1)), Some((CellState::Alive, 0)));
+
+        // ひとりぼっちのセルの残像は、trail_generations 世代で消える
+        let mut sparse = SparseField::new(Rule::conway());
+        sparse.trail_generations = 3;
+        sparse.cells.insert((0, 0), Cell::positional((0, 0)).alive_clone());
+        for dead_for in 1..3 {
+            sparse.iterate();
+            assert_eq!(sparse.cells[&(0, 0)].dead_for, dead_for);
+        }
+        sparse.iterate();
+        assert!(sparse.cells.is_empty());
+    }
+
+    #[test]
+    fn rejects_rules_that_fill_the_plane() {
+        assert!(SparseField::supports(&Rule::conway()));
+        assert!(!SparseField::supports(&"B0/S8".parse().unwrap()));
+        assert!(!SparseField::supports(&Rule::wireworld()));
+    }
+}
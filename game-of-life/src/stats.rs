@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+use std::fmt;
+
+// ある世代のフィールドの統計
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GenerationStats {
+    pub generation: u64,                      // 世代数
+    pub population: u64,                      // 生きているセルの数
+    pub births: u64,                          // 前の世代では生きていなかったが、この世代で生きているセルの数
+    pub deaths: u64,                          // 前の世代では生きていたが、この世代で生きていないセルの数
+    pub bounds: Option<(i64, i64, i64, i64)>, // 生きているセルを囲む最小の長方形 (min_x, min_y, max_x, max_y)
+}
+
+// 記録した統計全体の要約
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Summary {
+    pub first_generation: u64,   // 最初に記録した世代数
+    pub last: GenerationStats,   // 最後に記録した世代の統計
+    pub peak: GenerationStats,   // 生きているセルが最も多かった世代の統計（同数なら早い世代）
+    pub lowest: GenerationStats, // 生きているセルが最も少なかった世代の統計（同数なら早い世代）
+    pub births: u64,             // 記録した期間に生まれたセルの合計
+    pub deaths: u64,             // 記録した期間に死んだセルの合計
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "generations = {}..{}", self.first_generation, self.last.generation)?;
+        writeln!(f, "population = {}", self.last.population)?;
+        writeln!(f, "peak_population = {} (generation {})", self.peak.population, self.peak.generation)?;
+        writeln!(f, "lowest_population = {} (generation {})", self.lowest.population, self.lowest.generation)?;
+        writeln!(f, "births = {}", self.births)?;
+        writeln!(f, "deaths = {}", self.deaths)?;
+        match self.last.bounds {
+            Some((min_x, min_y, max_x, max_y)) => writeln!(f, "bounds = ({}, {})..({}, {})", min_x, min_y, max_x, max_y),
+            None => writeln!(f, "bounds = none"),
+        }
+    }
+}
+
+// 世代ごとの統計を時系列として記録するための構造体
+pub struct Stats {
+    pub records: Vec<GenerationStats>, // 記録した統計（世代数の昇順）
+    previous: HashSet<(i64, i64)>,     // 最後に記録した世代で生きていたセルの座標
+}
+
+impl Stats {
+    // generation世代目の生きているセルの座標aliveを起点に、統計の記録を始めるためのメソッド
+    pub fn new<I: IntoIterator<Item = (i64, i64)>>(generation: u64, alive: I) -> Stats {
+        let mut stats = Stats { records: vec![], previous: HashSet::new() };
+        stats.rewind(generation, alive);
+        stats
+    }
+
+    // generation世代目の生きているセルの座標aliveから統計を求めて記録するためのメソッド
+    // 記録済みの世代以前の世代が渡されたら、その世代以降の記録を捨ててから記録し直す
+    pub fn record<I: IntoIterator<Item = (i64, i64)>>(&mut self, generation: u64, alive: I) {
+        while self.records.last().is_some_and(|last| generation <= last.generation) {
+            self.records.pop();
+        }
+        let current: HashSet<(i64, i64)> = alive.into_iter().collect();
+        let bounds = current.iter().fold(None, |bounds, &(x, y)| match bounds {
+            None => Some((x, y, x, y)),
+            Some((min_x, min_y, max_x, max_y)) => Some((min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))),
+        });
+        self.records.push(GenerationStats {
+            generation,
+            population: current.len() as u64,
+            births: current.difference(&self.previous).count() as u64,
+            deaths: self.previous.difference(&current).count() as u64,
+            bounds,
+        });
+        self.previous = current;
+    }
+
+    // 過去のgeneration世代目に戻ったときに、その世代より後の記録を捨てて、そこから記録し直すためのメソッド
+    pub fn rewind<I: IntoIterator<Item = (i64, i64)>>(&mut self, generation: u64, alive: I) {
+        self.record(generation, alive);
+        // 戻った世代は前の記録から続いているとは限らないため、誕生・死滅の数は数えない
+        let last = self.records.last_mut().unwrap();
+        last.births = 0;
+        last.deaths = 0;
+    }
+
+    // 直近count世代ぶんの生きているセルの数
+    pub fn recent_populations(&self, count: usize) -> impl Iterator<Item = u64> + '_ {
+        self.records[self.records.len().saturating_sub(count)..].iter().map(|record| record.population)
+    }
+
+    // 記録したすべての統計を要約するためのメソッド
+    pub fn summary(&self) -> Summary {
+        let first = self.records[0];
+        let mut summary = Summary { first_generation: first.generation, last: first, peak: first, lowest: first, births: 0, deaths: 0 };
+        for record in &self.records[1..] {
+            if summary.peak.population < record.population {
+                summary.peak = *record;
+            }
+            if record.population < summary.lowest.population {
+                summary.lowest = *record;
+            }
+            summary.births += record.births;
+            summary.deaths += record.deaths;
+            summary.last = *record;
+        }
+        summary
+    }
+
+    // 記録したすべての統計をCSV形式の文字列に変換するためのメソッド
+    // 生きているセルが無い世代では、長方形の列は空にする
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("generation,population,births,deaths,min_x,min_y,max_x,max_y\n");
+        for record in &self.records {
+            let bounds = match record.bounds {
+                Some((min_x, min_y, max_x, max_y)) => format!("{},{},{},{}", min_x, min_y, max_x, max_y),
+                None => ",,,".to_string(),
+            };
+            csv += &format!("{},{},{},{},{}\n", record.generation, record.population, record.births, record.deaths, bounds);
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLINKER_H: [(i64, i64); 3] = [(1, 2), (2, 2), (3, 2)];
+    const BLINKER_V: [(i64, i64); 3] = [(2, 1), (2, 2), (2, 3)];
+
+    #[test]
+    fn counts_births_deaths_and_bounds() {
+        let mut stats = Stats::new(0, BLINKER_H);
+        stats.record(1, BLINKER_V);
+        stats.record(2, BLINKER_H);
+        stats.record(3, []);
+        assert_eq!(stats.records[0], GenerationStats { generation: 0, population: 3, births: 0, deaths: 0, bounds: Some((1, 2, 3, 2)) });
+        assert_eq!(stats.records[1], GenerationStats { generation: 1, population: 3, births: 2, deaths: 2, bounds: Some((2, 1, 2, 3)) });
+        assert_eq!(stats.records[3], GenerationStats { generation: 3, population: 0, births: 0, deaths: 3, bounds: None });
+        assert_eq!(stats.recent_populations(2).collect::<Vec<_>>(), vec![3, 0]);
+    }
+
+    #[test]
+    fn rewinding_drops_later_records() {
+        let mut stats = Stats::new(0, BLINKER_H);
+        for generation in 1..=5 {
+            stats.record(generation, if generation % 2 == 1 { BLINKER_V } else { BLINKER_H });
+        }
+        stats.rewind(2, BLINKER_H);
+        assert_eq!(stats.records.len(), 3);
+        stats.record(3, BLINKER_V);
+        assert_eq!(stats.records.iter().map(|record| record.generation).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert_eq!(stats.records[3].births, 2);
+    }
+
+    #[test]
+    fn summarises_the_records() {
+        let mut stats = Stats::new(0, BLINKER_H);
+        stats.record(1, BLINKER_V);
+        stats.record(2, [(0, 0), (1, 0), (2, 0), (3, 0)]);
+        stats.record(3, [(0, 0)]);
+        let summary = stats.summary();
+        assert_eq!(summary.first_generation, 0);
+        assert_eq!((summary.peak.generation, summary.peak.population), (2, 4));
+        assert_eq!((summary.lowest.generation, summary.lowest.population), (3, 1));
+        assert_eq!((summary.births, summary.deaths), (2 + 4, 2 + 3 + 3));
+        assert_eq!(
+            summary.to_string(),
+            "generations = 0..3\npopulation = 1\npeak_population = 4 (generation 2)\n\
+             lowest_population = 1 (generation 3)\nbirths = 6\ndeaths = 8\nbounds = (0, 0)..(0, 0)\n"
+        );
+    }
+
+    #[test]
+    fn writes_csv() {
+        let mut stats = Stats::new(0, [(0, 0), (5, -2)]);
+        stats.record(1, []);
+        assert_eq!(
+            stats.to_csv(),
+            "generation,population,births,deaths,min_x,min_y,max_x,max_y\n0,2,0,0,0,-2,5,0\n1,0,0,2,,,,\n"
+        );
+    }
+}
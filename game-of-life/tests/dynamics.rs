@@ -0,0 +1,137 @@
+// 小さなパターンをフィールドに置いて進め、よく知られた振る舞いをセル単位で確かめる回帰テスト
+// ウィンドウを開かずにライブラリだけを使うため、GPUやディスプレイの無いマシンでも `cargo test` で実行できる
+
+use game_of_life::boundary::Boundary;
+use game_of_life::pattern::Pattern;
+use game_of_life::period::{Outcome, PeriodDetector};
+use game_of_life::Field;
+
+// col_n列・row_n行の空のフィールドの(offset_x, offset_y)に、RLEの本体bodyのパターンを置く
+fn field_with(body: &str, col_n: u32, row_n: u32, (offset_x, offset_y): (i64, i64)) -> Field {
+    let pattern = Pattern::from_rle(&format!("x = {}, y = {}\n{}", col_n, row_n, body)).unwrap();
+    let mut field = Field::new(row_n, col_n, 0.0);
+    field.place_pattern(&pattern, offset_x, offset_y);
+    field
+}
+
+// 生きているセルの座標(x, y)を並べたもの
+fn alive_cells(field: &Field) -> Vec<(i32, i32)> {
+    let mut cells = vec![];
+    for x in 0..field.col_n as i32 {
+        for y in 0..field.row_n as i32 {
+            if field.alive_at(x, y) {
+                cells.push((x, y));
+            }
+        }
+    }
+    cells
+}
+
+// フィールドをgenerations世代進める
+fn advance(field: &mut Field, generations: u32) {
+    for _ in 0..generations {
+        field.iterate();
+    }
+}
+
+// 落ち着いた先の状態が検出されるまで進め、検出した状態を返す
+fn settle(field: &mut Field, max_generations: u32) -> Option<Outcome> {
+    let mut detector = PeriodDetector::new(64);
+    detector.observe(field);
+    for _ in 0..max_generations {
+        field.iterate();
+        if let Some(outcome) = detector.observe(field) {
+            return Some(outcome);
+        }
+    }
+    None
+}
+
+#[test]
+fn block_is_still() {
+    let mut field = field_with("2o$2o!", 6, 6, (2, 2));
+    let start = alive_cells(&field);
+    for _ in 0..10 {
+        field.iterate();
+        assert_eq!(alive_cells(&field), start);
+    }
+    assert_eq!(settle(&mut field, 1), Some(Outcome::StillLife));
+}
+
+#[test]
+fn blinker_has_period_two() {
+    let mut field = field_with("3o!", 5, 5, (1, 2));
+    let horizontal = alive_cells(&field);
+    assert_eq!(horizontal, [(1, 2), (2, 2), (3, 2)]);
+    field.iterate();
+    assert_eq!(alive_cells(&field), [(2, 1), (2, 2), (2, 3)]);
+    field.iterate();
+    assert_eq!(alive_cells(&field), horizontal);
+    assert_eq!(settle(&mut field, 4), Some(Outcome::Oscillator { period: 2 }));
+}
+
+#[test]
+fn glider_moves_one_cell_diagonally_every_four_generations() {
+    let mut field = field_with("bo$2bo$3o!", 20, 20, (1, 1));
+    let start = alive_cells(&field);
+    for cycle in 1..=10 {
+        advance(&mut field, 4);
+        let moved: Vec<(i32, i32)> = start.iter().map(|&(x, y)| (x + cycle, y + cycle)).collect();
+        assert_eq!(alive_cells(&field), moved, "after {} generations", cycle * 4);
+    }
+    // 途中の世代では同じ形にならない
+    field.iterate();
+    assert_eq!(field.population(), 5);
+    let shifted: Vec<(i32, i32)> = start.iter().map(|&(x, y)| (x + 10, y + 10)).collect();
+    assert_ne!(alive_cells(&field), shifted);
+}
+
+#[test]
+fn diehard_dies_out_at_generation_130() {
+    let mut field = field_with("6bo$2o$bo3b3o!", 40, 40, (16, 18));
+    assert_eq!(field.population(), 7);
+    advance(&mut field, 129);
+    assert!(0 < field.population(), "diehard must still be alive at generation 129");
+    field.iterate();
+    assert_eq!(field.population(), 0, "diehard must be gone at generation 130");
+    assert_eq!(settle(&mut field, 1), Some(Outcome::Extinct));
+}
+
+#[test]
+fn neighbour_counts_at_the_corners_of_a_full_field() {
+    // すべて生きている5列・4行のフィールド
+    let mut field = field_with("5o$5o$5o$5o!", 5, 4, (0, 0));
+    let corners = [(0, 0), (4, 0), (0, 3), (4, 3)];
+
+    // 端の外が死んでいるフィールドでは、角は3、辺は5、内側は8のセルに接している
+    field.boundary = Boundary::Dead;
+    for (x, y) in corners {
+        assert_eq!(field.alive_neighbour_count_from(x, y), 3, "corner ({}, {})", x, y);
+    }
+    assert_eq!(field.alive_neighbour_count_from(2, 0), 5);
+    assert_eq!(field.alive_neighbour_count_from(0, 2), 5);
+    assert_eq!(field.alive_neighbour_count_from(2, 2), 8);
+
+    // トーラスでは端がつながっているため、角も8つのセルに接している
+    field.boundary = Boundary::Torus;
+    for (x, y) in corners {
+        assert_eq!(field.alive_neighbour_count_from(x, y), 8, "corner ({}, {})", x, y);
+    }
+}
+
+#[test]
+fn a_lone_corner_cell_is_seen_by_the_opposite_corner_only_on_a_torus() {
+    let mut field = field_with("o!", 5, 4, (0, 0));
+    field.boundary = Boundary::Dead;
+    assert_eq!(field.alive_neighbour_count_from(4, 3), 0);
+    assert_eq!(field.alive_neighbour_count_from(1, 1), 1);
+    // 自分自身は数えない
+    assert_eq!(field.alive_neighbour_count_from(0, 0), 0);
+
+    field.boundary = Boundary::Torus;
+    for (x, y) in [(4, 3), (4, 0), (0, 3), (1, 3), (4, 1)] {
+        assert_eq!(field.alive_neighbour_count_from(x, y), 1, "cell ({}, {})", x, y);
+    }
+    assert_eq!(field.alive_neighbour_count_from(2, 2), 0);
+    assert_eq!(field.alive_neighbour_count_from(0, 0), 0);
+}